@@ -0,0 +1,115 @@
+use hprtree::{BBox, Point};
+
+use crate::filtered::Attributes;
+use crate::index::{contains, VisitQuery};
+use crate::packed::{LevelTree, NodeSummary};
+use crate::slab::bounds;
+
+// what a count/sum query gives back
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Aggregate {
+    pub count: usize,
+    pub population: u64,
+}
+
+impl Aggregate {
+    fn add(&mut self, other: &Aggregate) {
+        self.count += other.count;
+        self.population += other.population;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    bbox: [f32; 4],
+    total: Aggregate,
+}
+
+impl NodeSummary for Node {
+    fn bbox(&self) -> &[f32; 4] {
+        &self.bbox
+    }
+    fn combine(children: &[Node]) -> Node {
+        let boxes: Vec<[f32; 4]> = children.iter().map(|n| n.bbox).collect();
+        let mut total = Aggregate::default();
+        children.iter().for_each(|n| total.add(&n.total));
+        Node {
+            bbox: bounds(&boxes),
+            total,
+        }
+    }
+}
+
+// packed hilbert tree where every node knows the count and population below it. subtrees
+// inside the envelope are answered from that, only the boundary leaves look at points. the
+// elements themselves are not kept, just their points and populations.
+pub struct AggregateTree {
+    tree: LevelTree<Node, u64>,
+}
+
+impl AggregateTree {
+    pub const NAME: &'static str = "aggtree";
+
+    pub fn build<T: Attributes>(data: Vec<(T, Point)>) -> Self {
+        let data = data.into_iter().map(|(e, p)| (e.population(), p)).collect();
+        AggregateTree {
+            tree: LevelTree::build(data, |p, &population| Node {
+                bbox: [p.x, p.x, p.y, p.y],
+                total: Aggregate {
+                    count: 1,
+                    population,
+                },
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.points.len()
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.tree.size_in_bytes()
+    }
+
+    pub fn query_aggregate(&self, env: &BBox) -> Aggregate {
+        let mut res = Aggregate::default();
+        let mut stack: Vec<(usize, usize)> = self.tree.root().into_iter().collect();
+        while let Some((level, n)) = stack.pop() {
+            let node = &self.tree.levels[level][n];
+            let b = node.bbox();
+            if b[0] > env.maxx || b[1] < env.minx || b[2] > env.maxy || b[3] < env.miny {
+                continue;
+            }
+            if b[0] >= env.minx && b[1] <= env.maxx && b[2] >= env.miny && b[3] <= env.maxy {
+                res.add(&node.total);
+                continue;
+            }
+            let children = self.tree.children(level, n);
+            if level > 0 {
+                stack.extend(children.map(|c| (level - 1, c)));
+                continue;
+            }
+            for i in children {
+                if contains(env, &self.tree.points[i]) {
+                    res.count += 1;
+                    res.population += self.tree.items[i];
+                }
+            }
+        }
+        res
+    }
+}
+
+// the same answer from any backend by visiting every hit, the baseline
+pub fn visit_aggregate<T, I>(index: &I, env: &BBox) -> Aggregate
+where
+    T: Attributes,
+    I: VisitQuery<T>,
+{
+    let mut res = Aggregate::default();
+    index.query_visit(env, |e| {
+        res.count += 1;
+        res.population += e.population();
+    });
+    res
+}
@@ -0,0 +1,333 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use hprtree::{BBox, Point};
+
+use crate::index::{
+    clone_bbox, contains, extent_of, Counter, KnnQuery, RadiusQuery, SpatialIndex, VisitQuery,
+};
+use crate::quality::{Rect, TreeQuality};
+use crate::sphere::{
+    angle, cap_bound, haversine_m, normalize, radius_to_angle, to_xyz, LonLatRect,
+};
+
+// max number of points in a leaf
+const LEAF_SIZE: usize = 16;
+// angles of the bounds are widened by this, so rounding never prunes a hit
+const SLACK: f64 = 1e-9;
+
+// a cap on the unit sphere holding the points [start, end), inner nodes have two children
+struct Node {
+    center: [f64; 3],
+    radius: f64,
+    // lat/lon box of the cap, for envelope queries
+    bound: LonLatRect,
+    start: usize,
+    end: usize,
+    children: Option<(usize, usize)>,
+}
+
+// ball tree over the points as unit vectors, the metric is the great circle angle
+pub struct BallTree<T> {
+    nodes: Vec<Node>,
+    xyz: Vec<[f64; 3]>,
+    lon: Vec<f32>,
+    lat: Vec<f32>,
+    elements: Vec<T>,
+    extent: BBox,
+}
+
+fn build_node<T>(nodes: &mut Vec<Node>, items: &mut [([f64; 3], T, Point)], start: usize) -> usize {
+    let n = items.len() as f64;
+    let mut sum = [0f64; 3];
+    let mut lo = [f64::INFINITY; 3];
+    let mut hi = [f64::NEG_INFINITY; 3];
+    for (v, _, _) in items.iter() {
+        for d in 0..3 {
+            sum[d] += v[d];
+            lo[d] = lo[d].min(v[d]);
+            hi[d] = hi[d].max(v[d]);
+        }
+    }
+    // points spread over the whole sphere can average out to (almost) zero
+    let center = if sum.iter().map(|s| s.abs()).sum::<f64>() > 1e-9 * n {
+        normalize(sum)
+    } else {
+        items[0].0
+    };
+    let radius = items
+        .iter()
+        .map(|(v, _, _)| angle(&center, v))
+        .fold(0f64, f64::max)
+        + SLACK;
+    let id = nodes.len();
+    nodes.push(Node {
+        center,
+        radius,
+        bound: cap_bound(&center, radius),
+        start,
+        end: start + items.len(),
+        children: None,
+    });
+    if items.len() > LEAF_SIZE {
+        // split at the median of the axis with the largest spread
+        let axis = (0..3)
+            .max_by(|&a, &b| (hi[a] - lo[a]).total_cmp(&(hi[b] - lo[b])))
+            .unwrap();
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+        let (left, right) = items.split_at_mut(mid);
+        let left = build_node(nodes, left, start);
+        let right = build_node(nodes, right, start + mid);
+        nodes[id].children = Some((left, right));
+    }
+    id
+}
+
+// candidate for the knn result, ordered by distance
+struct Candidate(f64, usize);
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.total_cmp(&other.0) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// lat/lon box of a node's cap, the whole longitude range if it crosses the antimeridian
+fn node_rect(node: &Node) -> Rect {
+    let b = &node.bound;
+    let (lon_lo, lon_hi) = if b.lon_lo <= b.lon_hi {
+        (b.lon_lo, b.lon_hi)
+    } else {
+        (-180f64, 180f64)
+    };
+    [
+        lon_lo as f32,
+        lon_hi as f32,
+        b.lat_lo as f32,
+        b.lat_hi as f32,
+    ]
+}
+
+impl<T> BallTree<T> {
+    fn point(&self, i: usize) -> Point {
+        Point {
+            x: self.lon[i],
+            y: self.lat[i],
+        }
+    }
+}
+
+impl<T> SpatialIndex<T> for BallTree<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "balltree";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let mut items: Vec<([f64; 3], T, Point)> =
+            data.into_iter().map(|(e, p)| (to_xyz(&p), e, p)).collect();
+        let mut nodes = Vec::with_capacity(2 * items.len() / LEAF_SIZE + 1);
+        if !items.is_empty() {
+            build_node(&mut nodes, &mut items, 0);
+        }
+
+        let mut xyz = Vec::with_capacity(items.len());
+        let mut lon = Vec::with_capacity(items.len());
+        let mut lat = Vec::with_capacity(items.len());
+        let mut elements = Vec::with_capacity(items.len());
+        for (v, e, p) in items {
+            xyz.push(v);
+            lon.push(p.x);
+            lat.push(p.y);
+            elements.push(e);
+        }
+        BallTree {
+            nodes,
+            xyz,
+            lon,
+            lat,
+            elements,
+            extent,
+        }
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.nodes.capacity() * std::mem::size_of::<Node>()
+            + self.xyz.capacity() * std::mem::size_of::<[f64; 3]>()
+            + (self.lon.capacity() + self.lat.capacity()) * std::mem::size_of::<f32>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+    }
+    fn quality(&self) -> Option<TreeQuality> {
+        let mut quality = TreeQuality::default();
+        if self.nodes.is_empty() {
+            return Some(quality);
+        }
+        let mut stack = vec![(0, 0)];
+        while let Some((id, level)) = stack.pop() {
+            let node = &self.nodes[id];
+            match node.children {
+                Some((left, right)) => {
+                    let children = [node_rect(&self.nodes[left]), node_rect(&self.nodes[right])];
+                    quality.add_node(level, &node_rect(node), &children, 2);
+                    stack.push((left, level + 1));
+                    stack.push((right, level + 1));
+                }
+                None => quality.add_leaf(level, &node_rect(node), node.end - node.start, LEAF_SIZE),
+            }
+        }
+        Some(quality)
+    }
+}
+
+impl<T> VisitQuery<T> for BallTree<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            counter.nodes(1);
+            counter.bbox_tests(1);
+            if !node.bound.intersects(env) {
+                continue;
+            }
+            if node.bound.inside(env) {
+                self.elements[node.start..node.end]
+                    .iter()
+                    .for_each(&mut visit);
+                continue;
+            }
+            match node.children {
+                Some((left, right)) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                None => {
+                    counter.entries(node.end - node.start);
+                    for i in node.start..node.end {
+                        if contains(env, &self.point(i)) {
+                            visit(&self.elements[i]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T> RadiusQuery<T> for BallTree<T>
+where
+    T: Clone,
+{
+    fn query_radius(&self, center: &Point, radius_m: f64, list: &mut Vec<T>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let q = to_xyz(center);
+        let radius = radius_to_angle(radius_m);
+        let mut stack = vec![0];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            let d = angle(&q, &node.center);
+            if d - node.radius > radius + SLACK {
+                continue;
+            }
+            if d + node.radius < radius - SLACK {
+                list.extend_from_slice(&self.elements[node.start..node.end]);
+                continue;
+            }
+            match node.children {
+                Some((left, right)) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                None => {
+                    for i in node.start..node.end {
+                        // the final decision uses the same distance as every other backend
+                        if haversine_m(center, &self.point(i)) <= radius_m {
+                            list.push(self.elements[i].clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// exact, best first over the nodes by their smallest possible distance
+impl<T> KnnQuery<T> for BallTree<T>
+where
+    T: Clone,
+{
+    fn query_knn(&self, center: &Point, k: usize, list: &mut Vec<T>) {
+        if k == 0 || self.nodes.is_empty() {
+            return;
+        }
+        let q = to_xyz(center);
+        // max heap of the best k so far, min heap (by reversing) of the nodes to visit
+        let mut best: BinaryHeap<Candidate> = BinaryHeap::with_capacity(k + 1);
+        let mut queue = BinaryHeap::new();
+        queue.push(std::cmp::Reverse(Candidate(0f64, 0)));
+        while let Some(std::cmp::Reverse(Candidate(bound, id))) = queue.pop() {
+            if best.len() == k && bound > best.peek().unwrap().0 {
+                break;
+            }
+            let node = &self.nodes[id];
+            match node.children {
+                Some((left, right)) => {
+                    for child in [left, right] {
+                        let c = &self.nodes[child];
+                        let bound = (angle(&q, &c.center) - c.radius).max(0f64);
+                        queue.push(std::cmp::Reverse(Candidate(bound, child)));
+                    }
+                }
+                None => {
+                    for i in node.start..node.end {
+                        let d = angle(&q, &self.xyz[i]);
+                        if best.len() < k {
+                            best.push(Candidate(d, i));
+                        } else if d < best.peek().unwrap().0 {
+                            best.pop();
+                            best.push(Candidate(d, i));
+                        }
+                    }
+                }
+            }
+        }
+        list.extend(
+            best.into_sorted_vec()
+                .into_iter()
+                .map(|c| self.elements[c.1].clone()),
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use hprtree::{BBox, Point};
+
+use crate::index::{clone_bbox, extent_of, Counter, SpatialIndex, VisitQuery};
+use crate::packed::{hilbert_order, NODE_CAPACITY};
+use crate::quality::TreeQuality;
+use crate::slab::{bounds, levels_quality, ordered_bits};
+
+// where a leaf's points start in the bit stream and how many bits each delta takes
+struct Page {
+    offset: usize,
+    x_bits: u8,
+    y_bits: u8,
+}
+
+// bits needed for values up to v
+fn width(v: u32) -> u8 {
+    (32 - v.leading_zeros()) as u8
+}
+
+// packed tree whose leaves store every coordinate as the difference of its ordered float bits to
+// the ones of the leaf's lower bound, bit packed with the widths the leaf needs. lossless, so
+// queries compare the decoded words directly.
+pub struct CompressedTree<T> {
+    // node bounds level by level, leaves first, like the slab tree
+    levels: Vec<Vec<[f32; 4]>>,
+    pages: Vec<Page>,
+    // x and y deltas of each point after each other, one spare word at the end
+    bits: Vec<u64>,
+    elements: Vec<T>,
+    extent: BBox,
+}
+
+struct BitWriter {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, v: u32, w: u8) {
+        if w == 0 {
+            return;
+        }
+        let (i, s) = (self.len / 64, self.len % 64);
+        if i == self.words.len() {
+            self.words.push(0);
+        }
+        self.words[i] |= (v as u64) << s;
+        if s + w as usize > 64 {
+            self.words.push((v as u64) >> (64 - s));
+        }
+        self.len += w as usize;
+    }
+}
+
+#[inline]
+fn read(bits: &[u64], pos: usize, w: u8) -> u32 {
+    let (i, s) = (pos / 64, pos % 64);
+    // the spare word makes reading one past the last used word fine
+    let v = if s == 0 {
+        bits[i]
+    } else {
+        (bits[i] >> s) | (bits[i + 1] << (64 - s))
+    };
+    (v & ((1u64 << w) - 1)) as u32
+}
+
+impl<T> SpatialIndex<T> for CompressedTree<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "compressed";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let (points, elements) = hilbert_order(data, &extent);
+
+        let mut writer = BitWriter {
+            words: Vec::new(),
+            len: 0,
+        };
+        let mut pages = Vec::with_capacity(points.len().div_ceil(NODE_CAPACITY));
+        let mut level = Vec::with_capacity(pages.capacity());
+        for chunk in points.chunks(NODE_CAPACITY) {
+            let boxes: Vec<[f32; 4]> = chunk.iter().map(|p| [p.x, p.x, p.y, p.y]).collect();
+            let b = bounds(&boxes);
+            let (x0, y0) = (ordered_bits(b[0]), ordered_bits(b[2]));
+            let page = Page {
+                offset: writer.len,
+                x_bits: width(ordered_bits(b[1]) - x0),
+                y_bits: width(ordered_bits(b[3]) - y0),
+            };
+            for p in chunk {
+                writer.push(ordered_bits(p.x) - x0, page.x_bits);
+                writer.push(ordered_bits(p.y) - y0, page.y_bits);
+            }
+            pages.push(page);
+            level.push(b);
+        }
+        let mut levels = Vec::new();
+        while level.len() > 1 {
+            let next = level.chunks(NODE_CAPACITY).map(bounds).collect();
+            levels.push(level);
+            level = next;
+        }
+        if !level.is_empty() {
+            levels.push(level);
+        }
+        let mut bits = writer.words;
+        bits.push(0);
+        bits.shrink_to_fit();
+
+        CompressedTree {
+            levels,
+            pages,
+            bits,
+            elements,
+            extent,
+        }
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.levels.capacity() * std::mem::size_of::<Vec<[f32; 4]>>()
+            + self
+                .levels
+                .iter()
+                .map(|l| l.capacity() * std::mem::size_of::<[f32; 4]>())
+                .sum::<usize>()
+            + self.pages.capacity() * std::mem::size_of::<Page>()
+            + self.bits.capacity() * std::mem::size_of::<u64>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+    }
+    fn quality(&self) -> Option<TreeQuality> {
+        Some(levels_quality(&self.levels, self.elements.len()))
+    }
+}
+
+impl<T> VisitQuery<T> for CompressedTree<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if self.levels.is_empty() {
+            return;
+        }
+        let (qx0, qx1) = (ordered_bits(env.minx), ordered_bits(env.maxx));
+        let (qy0, qy1) = (ordered_bits(env.miny), ordered_bits(env.maxy));
+        let mut stack = vec![(self.levels.len() - 1, 0)];
+        while let Some((level, n)) = stack.pop() {
+            let b = &self.levels[level][n];
+            counter.nodes(1);
+            counter.bbox_tests(1);
+            if b[0] > env.maxx || b[1] < env.minx || b[2] > env.maxy || b[3] < env.miny {
+                continue;
+            }
+            let from = n * NODE_CAPACITY;
+            if level > 0 {
+                let to = (from + NODE_CAPACITY).min(self.levels[level - 1].len());
+                stack.extend((from..to).map(|c| (level - 1, c)));
+                continue;
+            }
+            let to = (from + NODE_CAPACITY).min(self.elements.len());
+            if b[0] >= env.minx && b[1] <= env.maxx && b[2] >= env.miny && b[3] <= env.maxy {
+                // the whole leaf is inside, no need to decode anything
+                self.elements[from..to].iter().for_each(&mut visit);
+                continue;
+            }
+            counter.entries(to - from);
+            let page = &self.pages[n];
+            let (x0, y0) = (ordered_bits(b[0]), ordered_bits(b[2]));
+            let mut pos = page.offset;
+            for i in from..to {
+                let x = x0 + read(&self.bits, pos, page.x_bits);
+                pos += page.x_bits as usize;
+                let y = y0 + read(&self.bits, pos, page.y_bits);
+                pos += page.y_bits as usize;
+                if x >= qx0 && x <= qx1 && y >= qy0 && y <= qy1 {
+                    visit(&self.elements[i]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use hprtree::{BBox, Point};
+
+use crate::index::{contains, SpatialIndex, VisitQuery};
+use crate::packed::{Bfs, LevelTree, NodeSummary, PackedTree};
+use crate::slab::bounds;
+
+// elements with the attributes the filters look at
+pub trait Attributes {
+    fn population(&self) -> u64;
+    fn country(&self) -> &str;
+}
+
+// population above min_population and, if given, in the country
+pub struct AttrFilter {
+    pub min_population: u64,
+    pub country: Option<String>,
+}
+
+impl AttrFilter {
+    pub fn name(&self) -> String {
+        match &self.country {
+            Some(country) => format!("pop{}_{country}", self.min_population),
+            None => format!("pop{}", self.min_population),
+        }
+    }
+
+    pub fn matches<T: Attributes>(&self, e: &T) -> bool {
+        e.population() > self.min_population
+            && self.country.as_ref().is_none_or(|c| c == e.country())
+    }
+}
+
+// an attribute filtered envelope query and how it gets built
+pub trait FilteredQuery<T> {
+    // used for the result/ directories
+    const NAME: &'static str;
+
+    fn build(data: Vec<(T, Point)>) -> Self;
+    fn query_filtered(&self, env: &BBox, filter: &AttrFilter, list: &mut Vec<T>);
+}
+
+// the spatial query as it is, the attributes are checked on its hits
+pub struct PostFilter<T> {
+    index: PackedTree<T, Bfs>,
+}
+
+impl<T> FilteredQuery<T> for PostFilter<T>
+where
+    T: Clone + Attributes,
+{
+    const NAME: &'static str = "postfilter";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        PostFilter {
+            index: PackedTree::build(data),
+        }
+    }
+    fn query_filtered(&self, env: &BBox, filter: &AttrFilter, list: &mut Vec<T>) {
+        self.index.query_visit(env, |e| {
+            if filter.matches(e) {
+                list.push(e.clone());
+            }
+        });
+    }
+}
+
+// decimal order of magnitude, population 0 to 9 is class 0
+fn population_class(population: u64) -> u32 {
+    population.checked_ilog10().unwrap_or(0)
+}
+
+// one index per country and population class. a query only looks at the classes that can hold
+// populations above the minimum, and at one country if it names one.
+pub struct Partitioned<T> {
+    parts: HashMap<(String, u32), PackedTree<T, Bfs>>,
+}
+
+impl<T> FilteredQuery<T> for Partitioned<T>
+where
+    T: Clone + Attributes,
+{
+    const NAME: &'static str = "partitioned";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let mut groups: HashMap<(String, u32), Vec<(T, Point)>> = HashMap::new();
+        for (e, p) in data {
+            let key = (e.country().to_string(), population_class(e.population()));
+            groups.entry(key).or_default().push((e, p));
+        }
+        Partitioned {
+            parts: groups
+                .into_iter()
+                .map(|(key, data)| (key, PackedTree::build(data)))
+                .collect(),
+        }
+    }
+    fn query_filtered(&self, env: &BBox, filter: &AttrFilter, list: &mut Vec<T>) {
+        let min_class = population_class(filter.min_population);
+        for ((country, class), index) in &self.parts {
+            if *class < min_class || filter.country.as_ref().is_some_and(|c| c != country) {
+                continue;
+            }
+            if *class > min_class {
+                // every population of a higher class is above the minimum
+                index.query_with_list(env, list);
+            } else {
+                index.query_visit(env, |e| {
+                    if filter.matches(e) {
+                        list.push(e.clone());
+                    }
+                });
+            }
+        }
+    }
+}
+
+// what a node knows about the attributes below it
+#[derive(Clone, Copy)]
+struct Summary {
+    bbox: [f32; 4],
+    min_population: u64,
+    max_population: u64,
+    // one bit per country, hashed into 64
+    countries: u64,
+}
+
+fn country_bit(country: &str) -> u64 {
+    // fnv-1a
+    let mut h = 0xcbf29ce484222325u64;
+    for b in country.bytes() {
+        h = (h ^ b as u64).wrapping_mul(0x100000001b3);
+    }
+    1 << (h % 64)
+}
+
+impl NodeSummary for Summary {
+    fn bbox(&self) -> &[f32; 4] {
+        &self.bbox
+    }
+    fn combine(children: &[Summary]) -> Summary {
+        let boxes: Vec<[f32; 4]> = children.iter().map(|s| s.bbox).collect();
+        Summary {
+            bbox: bounds(&boxes),
+            min_population: children.iter().map(|s| s.min_population).min().unwrap(),
+            max_population: children.iter().map(|s| s.max_population).max().unwrap(),
+            countries: children.iter().fold(0, |m, s| m | s.countries),
+        }
+    }
+}
+
+// packed tree like the slab tree whose nodes also carry the population range and the countries
+// below them. subtrees that can't match the filter are pruned along with the ones outside the
+// envelope, the ones that all match are taken whole.
+pub struct SummaryTree<T> {
+    tree: LevelTree<Summary, T>,
+}
+
+impl<T> FilteredQuery<T> for SummaryTree<T>
+where
+    T: Clone + Attributes,
+{
+    const NAME: &'static str = "summarytree";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        SummaryTree {
+            tree: LevelTree::build(data, |p, e| Summary {
+                bbox: [p.x, p.x, p.y, p.y],
+                min_population: e.population(),
+                max_population: e.population(),
+                countries: country_bit(e.country()),
+            }),
+        }
+    }
+    fn query_filtered(&self, env: &BBox, filter: &AttrFilter, list: &mut Vec<T>) {
+        let tree = &self.tree;
+        let countries = filter.country.as_deref().map_or(u64::MAX, country_bit);
+        let mut stack: Vec<(usize, usize)> = tree.root().into_iter().collect();
+        while let Some((level, n)) = stack.pop() {
+            let s = &tree.levels[level][n];
+            let b = s.bbox();
+            if b[0] > env.maxx || b[1] < env.minx || b[2] > env.maxy || b[3] < env.miny {
+                continue;
+            }
+            if s.max_population <= filter.min_population || s.countries & countries == 0 {
+                continue;
+            }
+            let inside =
+                b[0] >= env.minx && b[1] <= env.maxx && b[2] >= env.miny && b[3] <= env.maxy;
+            if inside && s.min_population > filter.min_population && filter.country.is_none() {
+                list.extend_from_slice(&tree.items[tree.items_below(level, n)]);
+                continue;
+            }
+            let children = tree.children(level, n);
+            if level > 0 {
+                stack.extend(children.map(|c| (level - 1, c)));
+                continue;
+            }
+            for i in children {
+                let e = &tree.items[i];
+                if contains(env, &tree.points[i]) && filter.matches(e) {
+                    list.push(e.clone());
+                }
+            }
+        }
+    }
+}
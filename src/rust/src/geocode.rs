@@ -0,0 +1,166 @@
+use csv::StringRecord;
+use hprtree::Point;
+use rstar::{RTreeObject, AABB};
+
+use crate::index::{KnnQuery, Located, SpatialIndex};
+use crate::sphere::haversine_m;
+
+// a populated place of the opendatasoft geonames file
+#[derive(Clone, Debug)]
+pub struct Place {
+    pub name: String,
+    // iso 3166 alpha-2
+    pub country: String,
+    pub population: u64,
+    pub lat: f32,
+    pub lon: f32,
+}
+
+impl Located for Place {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl RTreeObject for Place {
+    type Envelope = AABB<[f32; 2]>;
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_point([self.lon, self.lat])
+    }
+}
+
+// name in column 1, country code in 6, population in 13, "lat, lon" in the last one
+pub fn opendata_place(record: StringRecord) -> Option<(Place, Point)> {
+    assert!(record.len() == 20);
+    let (lat, lon) = record.get(record.len() - 1)?.split_once(", ")?;
+    let (lat, lon) = (lat.parse::<f32>().ok()?, lon.parse::<f32>().ok()?);
+    Some((
+        Place {
+            name: record.get(1)?.to_string(),
+            country: record.get(6)?.to_string(),
+            population: record.get(13)?.parse().unwrap_or(0),
+            lat,
+            lon,
+        },
+        Point { x: lon, y: lat },
+    ))
+}
+
+// nearest places to a coordinate over any backend with knn queries. exact as long as the
+// backend's knn is, the ball tree, hprtree and rstar ones are.
+pub struct ReverseGeocoder<I> {
+    index: I,
+}
+
+impl<I> ReverseGeocoder<I>
+where
+    I: SpatialIndex<Place> + KnnQuery<Place>,
+{
+    pub fn new(places: Vec<(Place, Point)>) -> Self {
+        ReverseGeocoder {
+            index: I::build(places),
+        }
+    }
+
+    pub fn nearest(&self, p: &Point) -> Option<(Place, f64)> {
+        self.k_nearest(p, 1).pop()
+    }
+
+    // up to k places with their great circle distances in meters, nearest first
+    pub fn k_nearest(&self, p: &Point, k: usize) -> Vec<(Place, f64)> {
+        let mut places = Vec::with_capacity(k);
+        self.index.query_knn(p, k, &mut places);
+        let mut res: Vec<(Place, f64)> = places
+            .into_iter()
+            .map(|place| {
+                let d = haversine_m(p, &place.location());
+                (place, d)
+            })
+            .collect();
+        res.sort_by(|a, b| a.1.total_cmp(&b.1));
+        res
+    }
+}
+
+// lon and lat of every row, taken from the columns named so. without such a header the file
+// has none and they are the first two columns.
+pub fn read_points(path: &str) -> Vec<Point> {
+    let mut reader = csv::Reader::from_path(path).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let (x, y) = match (column("lon"), column("lat")) {
+        (None, None) => {
+            reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_path(path)
+                .unwrap();
+            (0, 1)
+        }
+        (x, y) => (x.unwrap_or(0), y.unwrap_or(1)),
+    };
+    reader
+        .records()
+        .map(|r| {
+            let r = r.unwrap();
+            Point {
+                x: r.get(x).unwrap().trim().parse().unwrap(),
+                y: r.get(y).unwrap().trim().parse().unwrap(),
+            }
+        })
+        .collect()
+}
+
+// one row per query point and result rank
+pub fn write_results<I>(geocoder: &ReverseGeocoder<I>, points: &[Point], k: usize, path: &str)
+where
+    I: SpatialIndex<Place> + KnnQuery<Place>,
+{
+    let mut writer = csv::Writer::from_path(path).unwrap();
+    writer
+        .write_record([
+            "lon",
+            "lat",
+            "rank",
+            "name",
+            "country",
+            "population",
+            "distance_m",
+        ])
+        .unwrap();
+    for p in points {
+        let results = if k == 1 {
+            geocoder.nearest(p).into_iter().collect()
+        } else {
+            geocoder.k_nearest(p, k)
+        };
+        for (rank, (place, d)) in results.iter().enumerate() {
+            writer
+                .write_record([
+                    &p.x.to_string(),
+                    &p.y.to_string(),
+                    &(rank + 1).to_string(),
+                    &place.name,
+                    &place.country,
+                    &place.population.to_string(),
+                    &format!("{d:.0}"),
+                ])
+                .unwrap();
+        }
+    }
+}
+
+// the batch mode: k nearest places of every point in the input csv into the output csv
+pub fn geocode_file<I>(places: Vec<(Place, Point)>, input: &str, output: &str, k: usize)
+where
+    I: SpatialIndex<Place> + KnnQuery<Place>,
+{
+    let geocoder = ReverseGeocoder::<I>::new(places);
+    write_results(&geocoder, &read_points(input), k, output);
+}
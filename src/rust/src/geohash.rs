@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use hprtree::{BBox, Point};
+
+use crate::index::{
+    clone_bbox, contains, extent_of, intersects, Counter, SpatialIndex, VisitQuery,
+};
+
+// average number of points per bucket the automatic precision aims for
+const GEOHASH_BUCKET_TARGET: usize = 4;
+// 12 characters * 5 bits still fit into a u64
+const GEOHASH_MAX_PRECISION: u32 = 12;
+
+// points bucketed by their geohash prefix of a fixed length (in geohash characters)
+pub struct GeohashIndex<T> {
+    lonbits: u32,
+    latbits: u32,
+    buckets: HashMap<u64, Vec<(T, Point)>>,
+    extent: BBox,
+    len: usize,
+}
+
+impl<T> GeohashIndex<T> {
+    pub fn with_precision(data: Vec<(T, Point)>, precision: u32) -> Self {
+        let precision = precision.clamp(1, GEOHASH_MAX_PRECISION);
+        let bits = precision * 5;
+        let mut index = GeohashIndex {
+            // geohash starts with a longitude bit, so longitude gets the extra one
+            lonbits: bits.div_ceil(2),
+            latbits: bits / 2,
+            buckets: HashMap::new(),
+            extent: extent_of(data.iter().map(|e| &e.1)),
+            len: data.len(),
+        };
+        for e in data {
+            let hash = index.hash(index.cell_x(e.1.x), index.cell_y(e.1.y));
+            index.buckets.entry(hash).or_default().push(e);
+        }
+        for bucket in index.buckets.values_mut() {
+            bucket.shrink_to_fit();
+        }
+        index
+    }
+
+    // smallest precision at which a uniform spread over the globe fills buckets to the target
+    pub fn auto_precision(n: usize) -> u32 {
+        let cells = (n / GEOHASH_BUCKET_TARGET).max(1) as f64;
+        ((cells.log2() / 5f64).ceil() as u32).clamp(1, GEOHASH_MAX_PRECISION)
+    }
+
+    fn cell_x(&self, lon: f32) -> u64 {
+        let cells = 1u64 << self.lonbits;
+        (((lon as f64 + 180f64) / 360f64 * cells as f64) as u64).min(cells - 1)
+    }
+
+    fn cell_y(&self, lat: f32) -> u64 {
+        let cells = 1u64 << self.latbits;
+        (((lat as f64 + 90f64) / 180f64 * cells as f64) as u64).min(cells - 1)
+    }
+
+    // interleave the cell coordinates the way geohash does, starting with longitude
+    fn hash(&self, x: u64, y: u64) -> u64 {
+        let mut hash = 0u64;
+        for k in 0..(self.lonbits + self.latbits) {
+            let bit = if k % 2 == 0 {
+                (x >> (self.lonbits - 1 - k / 2)) & 1
+            } else {
+                (y >> (self.latbits - 1 - k / 2)) & 1
+            };
+            hash = (hash << 1) | bit;
+        }
+        hash
+    }
+}
+
+impl<T> SpatialIndex<T> for GeohashIndex<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "geohash";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let precision = GeohashIndex::<T>::auto_precision(data.len());
+        GeohashIndex::with_precision(data, precision)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        // hashbrown keeps one control byte per slot next to the slot itself
+        std::mem::size_of_val(self)
+            + self.buckets.capacity() * (std::mem::size_of::<(u64, Vec<(T, Point)>)>() + 1)
+            + self
+                .buckets
+                .values()
+                .map(|b| b.capacity() * std::mem::size_of::<(T, Point)>())
+                .sum::<usize>()
+    }
+}
+
+impl<T> VisitQuery<T> for GeohashIndex<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        counter.bbox_tests(1);
+        if env.minx > env.maxx || env.miny > env.maxy || !intersects(env, &self.extent) {
+            return;
+        }
+        let (x0, x1) = (self.cell_x(env.minx), self.cell_x(env.maxx));
+        let (y0, y1) = (self.cell_y(env.miny), self.cell_y(env.maxy));
+        let ncells = (x1 - x0 + 1) * (y1 - y0 + 1);
+        if ncells as usize > self.buckets.len() {
+            // large envelopes cover more cells than there are buckets, just scan everything
+            for bucket in self.buckets.values() {
+                counter.nodes(1);
+                counter.entries(bucket.len());
+                bucket
+                    .iter()
+                    .filter(|e| contains(env, &e.1))
+                    .for_each(|e| visit(&e.0));
+            }
+            return;
+        }
+        for cy in y0..=y1 {
+            for cx in x0..=x1 {
+                // every lookup counts, empty cells included
+                counter.nodes(1);
+                let bucket = match self.buckets.get(&self.hash(cx, cy)) {
+                    Some(bucket) => bucket,
+                    None => continue,
+                };
+                if cx > x0 && cx < x1 && cy > y0 && cy < y1 {
+                    bucket.iter().for_each(|e| visit(&e.0));
+                } else {
+                    counter.entries(bucket.len());
+                    bucket
+                        .iter()
+                        .filter(|e| contains(env, &e.1))
+                        .for_each(|e| visit(&e.0));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use hprtree::{BBox, Point};
+
+use crate::index::{
+    clone_bbox, contains, extent_of, intersects, Counter, SpatialIndex, VisitQuery,
+};
+
+// average number of points per cell the automatic resolution aims for
+const GRID_CELL_TARGET: usize = 4;
+
+pub enum GridResolution {
+    Auto,
+    Fixed(usize, usize),
+}
+
+// fixed resolution lon/lat grid over the data extent, one vector per cell
+pub struct UniformGrid<T> {
+    extent: BBox,
+    nx: usize,
+    ny: usize,
+    cellw: f32,
+    cellh: f32,
+    cells: Vec<Vec<(T, Point)>>,
+    len: usize,
+}
+
+impl<T> UniformGrid<T> {
+    pub fn with_resolution(data: Vec<(T, Point)>, resolution: GridResolution) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let w = extent.maxx - extent.minx;
+        let h = extent.maxy - extent.miny;
+        let (nx, ny) = match resolution {
+            GridResolution::Fixed(nx, ny) => (nx.max(1), ny.max(1)),
+            GridResolution::Auto => {
+                let cells = (data.len() / GRID_CELL_TARGET).max(1);
+                if w <= 0f32 {
+                    (1, cells)
+                } else if h <= 0f32 {
+                    (cells, 1)
+                } else {
+                    let nx = ((cells as f32 * w / h).sqrt().round() as usize).clamp(1, cells);
+                    (nx, cells.div_ceil(nx))
+                }
+            }
+        };
+        let mut grid = UniformGrid {
+            cellw: if w > 0f32 { w / nx as f32 } else { 1f32 },
+            cellh: if h > 0f32 { h / ny as f32 } else { 1f32 },
+            extent,
+            nx,
+            ny,
+            cells: Vec::new(),
+            len: data.len(),
+        };
+
+        // count first so every cell vector is allocated exactly once
+        let mut counts = vec![0usize; nx * ny];
+        for e in &data {
+            counts[grid.cell_of(&e.1)] += 1;
+        }
+        grid.cells = counts.into_iter().map(Vec::with_capacity).collect();
+        for e in data {
+            let c = grid.cell_of(&e.1);
+            grid.cells[c].push(e);
+        }
+        grid
+    }
+
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.nx, self.ny)
+    }
+
+    // float to int casts saturate, so anything left of / below the extent lands in cell 0
+    fn cell_x(&self, x: f32) -> usize {
+        (((x - self.extent.minx) / self.cellw) as usize).min(self.nx - 1)
+    }
+
+    fn cell_y(&self, y: f32) -> usize {
+        (((y - self.extent.miny) / self.cellh) as usize).min(self.ny - 1)
+    }
+
+    fn cell_of(&self, p: &Point) -> usize {
+        self.cell_y(p.y) * self.nx + self.cell_x(p.x)
+    }
+}
+
+impl<T> SpatialIndex<T> for UniformGrid<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "grid";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        UniformGrid::with_resolution(data, GridResolution::Auto)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.cells.capacity() * std::mem::size_of::<Vec<(T, Point)>>()
+            + self
+                .cells
+                .iter()
+                .map(|c| c.capacity() * std::mem::size_of::<(T, Point)>())
+                .sum::<usize>()
+    }
+}
+
+impl<T> VisitQuery<T> for UniformGrid<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        counter.bbox_tests(1);
+        if !intersects(env, &self.extent) {
+            return;
+        }
+        let (x0, x1) = (self.cell_x(env.minx), self.cell_x(env.maxx));
+        let (y0, y1) = (self.cell_y(env.miny), self.cell_y(env.maxy));
+        for cy in y0..=y1 {
+            for cx in x0..=x1 {
+                let cell = &self.cells[cy * self.nx + cx];
+                counter.nodes(1);
+                // the cell mapping is monotonic, so cells strictly inside the range are fully covered
+                if cx > x0 && cx < x1 && cy > y0 && cy < y1 {
+                    cell.iter().for_each(|e| visit(&e.0));
+                } else {
+                    counter.entries(cell.len());
+                    cell.iter()
+                        .filter(|e| contains(env, &e.1))
+                        .for_each(|e| visit(&e.0));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use hprtree::{BBox, Point};
+
+use crate::index::{clone_bbox, contains, extent_of, Counter, KnnQuery, SpatialIndex, VisitQuery};
+use crate::sphere::{cap_bound, dot, haversine_m, normalize, to_lonlat, to_xyz};
+
+pub const MAX_RES: u8 = 15;
+// hex circumradius at resolution 0 in gnomonic plane units (radians near the face center)
+const RES0_SIZE: f64 = 0.5;
+// every resolution has 7 times as many cells as the one before, like h3
+const APERTURE: f64 = 7f64;
+// average number of points per cell the automatic resolution aims for
+const HEX_CELL_TARGET: usize = 4;
+const AXIAL_OFFSET: i64 = 1 << 26;
+const AXIAL_DIRECTIONS: [(i64, i64); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+// one of the 20 icosahedron faces with the basis of its gnomonic tangent plane
+struct Face {
+    center: [f64; 3],
+    e1: [f64; 3],
+    e2: [f64; 3],
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn faces() -> &'static [Face] {
+    static FACES: OnceLock<Vec<Face>> = OnceLock::new();
+    FACES.get_or_init(|| {
+        let phi = (1f64 + 5f64.sqrt()) / 2f64;
+        let mut vertices = Vec::with_capacity(12);
+        for a in [-1f64, 1f64] {
+            for b in [-phi, phi] {
+                vertices.push([0f64, a, b]);
+                vertices.push([a, b, 0f64]);
+                vertices.push([b, 0f64, a]);
+            }
+        }
+        // three vertices form a face iff they are pairwise one edge (length 2) apart
+        let adjacent = |a: &[f64; 3], b: &[f64; 3]| {
+            let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+            (dot(&d, &d) - 4f64).abs() < 1e-9
+        };
+        let mut faces = Vec::with_capacity(20);
+        for i in 0..12 {
+            for j in (i + 1)..12 {
+                for k in (j + 1)..12 {
+                    let (a, b, c) = (&vertices[i], &vertices[j], &vertices[k]);
+                    if adjacent(a, b) && adjacent(b, c) && adjacent(a, c) {
+                        let center =
+                            normalize([a[0] + b[0] + c[0], a[1] + b[1] + c[1], a[2] + b[2] + c[2]]);
+                        let a = normalize(*a);
+                        let d = dot(&a, &center);
+                        let e1 = normalize([
+                            a[0] - center[0] * d,
+                            a[1] - center[1] * d,
+                            a[2] - center[2] * d,
+                        ]);
+                        let e2 = cross(&center, &e1);
+                        faces.push(Face { center, e1, e2 });
+                    }
+                }
+            }
+        }
+        assert!(faces.len() == 20);
+        faces
+    })
+}
+
+// the face a point belongs to is the one with the nearest center
+fn home_face(v: &[f64; 3]) -> usize {
+    let mut best = 0;
+    let mut best_dot = f64::NEG_INFINITY;
+    for (f, face) in faces().iter().enumerate() {
+        let d = dot(v, &face.center);
+        if d > best_dot {
+            best = f;
+            best_dot = d;
+        }
+    }
+    best
+}
+
+fn project(face: usize, v: &[f64; 3]) -> (f64, f64) {
+    let face = &faces()[face];
+    let d = dot(v, &face.center);
+    (dot(v, &face.e1) / d, dot(v, &face.e2) / d)
+}
+
+fn unproject(face: usize, x: f64, y: f64) -> [f64; 3] {
+    let face = &faces()[face];
+    normalize([
+        face.center[0] + x * face.e1[0] + y * face.e2[0],
+        face.center[1] + x * face.e1[1] + y * face.e2[1],
+        face.center[2] + x * face.e1[2] + y * face.e2[2],
+    ])
+}
+
+pub fn cell_size(res: u8) -> f64 {
+    RES0_SIZE / APERTURE.sqrt().powi(res as i32)
+}
+
+// resolution whose cells hold about HEX_CELL_TARGET of n points spread over the whole sphere
+pub fn auto_resolution(n: usize) -> u8 {
+    let cells = (n / HEX_CELL_TARGET).max(1) as f64;
+    let hex_area = 4f64 * std::f64::consts::PI / cells;
+    let size = (hex_area / (1.5 * 3f64.sqrt())).sqrt();
+    ((RES0_SIZE / size).ln() / APERTURE.sqrt().ln())
+        .round()
+        .clamp(0f64, MAX_RES as f64) as u8
+}
+
+// cube rounding of fractional axial coordinates
+fn axial_round(q: f64, r: f64) -> (i64, i64) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as i64, rr as i64)
+}
+
+// pointy top hexagon (resolution, icosahedron face, axial q/r in that face's gnomonic plane).
+// a hex cut by a face edge exists once per face, like the overage handling of h3 the parts are
+// separate cells. parents are the cells containing the center, so the hierarchy is not exact.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct HexCell(pub u64);
+
+impl HexCell {
+    fn new(res: u8, face: usize, q: i64, r: i64) -> Self {
+        HexCell(
+            ((res as u64) << 59)
+                | ((face as u64) << 54)
+                | (((q + AXIAL_OFFSET) as u64) << 27)
+                | (r + AXIAL_OFFSET) as u64,
+        )
+    }
+
+    fn in_face(face: usize, v: &[f64; 3], res: u8) -> Self {
+        let (x, y) = project(face, v);
+        let size = cell_size(res);
+        let q = (3f64.sqrt() / 3f64 * x - y / 3f64) / size;
+        let r = (2f64 / 3f64 * y) / size;
+        let (q, r) = axial_round(q, r);
+        HexCell::new(res, face, q, r)
+    }
+
+    pub fn from_point(p: &Point, res: u8) -> Self {
+        let v = to_xyz(p);
+        HexCell::in_face(home_face(&v), &v, res)
+    }
+
+    pub fn res(&self) -> u8 {
+        (self.0 >> 59) as u8
+    }
+
+    fn face(&self) -> usize {
+        ((self.0 >> 54) & 0x1F) as usize
+    }
+
+    fn axial(&self) -> (i64, i64) {
+        let mask = (1u64 << 27) - 1;
+        (
+            ((self.0 >> 27) & mask) as i64 - AXIAL_OFFSET,
+            (self.0 & mask) as i64 - AXIAL_OFFSET,
+        )
+    }
+
+    fn plane_center(&self) -> (f64, f64) {
+        let (q, r) = self.axial();
+        let size = cell_size(self.res());
+        (
+            size * 3f64.sqrt() * (q as f64 + r as f64 / 2f64),
+            size * 1.5 * r as f64,
+        )
+    }
+
+    fn center_xyz(&self) -> [f64; 3] {
+        let (x, y) = self.plane_center();
+        unproject(self.face(), x, y)
+    }
+
+    pub fn center(&self) -> Point {
+        let (lon, lat) = to_lonlat(&self.center_xyz());
+        Point {
+            x: lon as f32,
+            y: lat as f32,
+        }
+    }
+
+    // the six corners as lon/lat, counterclockwise
+    pub fn boundary(&self) -> Vec<Point> {
+        let (x, y) = self.plane_center();
+        let size = cell_size(self.res());
+        (0..6)
+            .map(|k| {
+                let a = (30f64 + 60f64 * k as f64).to_radians();
+                let (lon, lat) = to_lonlat(&unproject(
+                    self.face(),
+                    x + size * a.cos(),
+                    y + size * a.sin(),
+                ));
+                Point {
+                    x: lon as f32,
+                    y: lat as f32,
+                }
+            })
+            .collect()
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        if self.res() == 0 {
+            return None;
+        }
+        let v = self.center_xyz();
+        Some(HexCell::in_face(home_face(&v), &v, self.res() - 1))
+    }
+
+    // all cells one resolution finer whose parent is this cell
+    pub fn children(&self) -> Vec<Self> {
+        if self.res() == MAX_RES {
+            return Vec::new();
+        }
+        let (x, y) = self.plane_center();
+        let res = self.res() + 1;
+        let reach = ((cell_size(self.res()) / cell_size(res)).ceil() as i64) + 2;
+        let center = HexCell::in_face(self.face(), &unproject(self.face(), x, y), res);
+        let (cq, cr) = center.axial();
+        let mut children = Vec::new();
+        for dq in -reach..=reach {
+            for dr in -reach..=reach {
+                let child = HexCell::new(res, self.face(), cq + dq, cr + dr);
+                let v = child.center_xyz();
+                let child = HexCell::in_face(home_face(&v), &v, res);
+                if child.parent() == Some(*self) && !children.contains(&child) {
+                    children.push(child);
+                }
+            }
+        }
+        children
+    }
+
+    // neighbours are found through the sphere, so they continue across face edges
+    pub fn neighbours(&self) -> Vec<Self> {
+        let (x, y) = self.plane_center();
+        let size = cell_size(self.res());
+        let mut neighbours = Vec::with_capacity(6);
+        for (dq, dr) in AXIAL_DIRECTIONS {
+            let nx = x + size * 3f64.sqrt() * (dq as f64 + dr as f64 / 2f64);
+            let ny = y + size * 1.5 * dr as f64;
+            let v = unproject(self.face(), nx, ny);
+            let n = HexCell::in_face(home_face(&v), &v, self.res());
+            if n != *self && !neighbours.contains(&n) {
+                neighbours.push(n);
+            }
+        }
+        neighbours
+    }
+
+    // every cell within k neighbour steps, including this one
+    pub fn k_ring(&self, k: usize) -> Vec<Self> {
+        let mut seen = HashSet::new();
+        seen.insert(*self);
+        let mut ring = vec![*self];
+        let mut result = vec![*self];
+        for _ in 0..k {
+            let mut next = Vec::new();
+            for cell in &ring {
+                for n in cell.neighbours() {
+                    if seen.insert(n) {
+                        next.push(n);
+                    }
+                }
+            }
+            result.extend(next.iter().copied());
+            ring = next;
+        }
+        result
+    }
+}
+
+// cells of the resolution that together cover env, every point of env lies in one of them
+pub fn cover_bbox(env: &BBox, res: u8) -> Vec<HexCell> {
+    let size = cell_size(res);
+    // gnomonic planes only stretch, so samples half a cell apart (in angle) are never further
+    // than a cell from any point and its cell is at most one step away from a sample's cell
+    let step = size / 2f64;
+    let (lat0, lat1) = (
+        (env.miny as f64).to_radians(),
+        (env.maxy as f64).to_radians(),
+    );
+    let (lon0, lon1) = (
+        (env.minx as f64).to_radians(),
+        (env.maxx as f64).to_radians(),
+    );
+    let mut cells = HashSet::new();
+    let rows = ((lat1 - lat0) / step).ceil().max(1f64) as usize;
+    for row in 0..=rows {
+        let lat = lat0 + (lat1 - lat0) * row as f64 / rows as f64;
+        let lonstep = step / lat.cos().max(1e-9);
+        let cols = ((lon1 - lon0) / lonstep).ceil().clamp(1f64, 1e7) as usize;
+        for col in 0..=cols {
+            let lon = lon0 + (lon1 - lon0) * col as f64 / cols as f64;
+            let v = [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+            // points close by may belong to a neighbouring face, so look at those too
+            let best = dot(&v, &faces()[home_face(&v)].center);
+            for (f, face) in faces().iter().enumerate() {
+                if dot(&v, &face.center) >= best - 2f64 * step {
+                    let cell = HexCell::in_face(f, &v, res);
+                    cells.insert(cell);
+                    let (q, r) = cell.axial();
+                    for (dq, dr) in AXIAL_DIRECTIONS {
+                        cells.insert(HexCell::new(res, f, q + dq, r + dr));
+                    }
+                }
+            }
+        }
+    }
+    let mut cells: Vec<HexCell> = cells.into_iter().collect();
+    cells.sort_unstable();
+    cells
+}
+
+// replaces every complete set of children by its parent, repeatedly. approximate: parents are
+// found by cell centres, so a parent doesn't cover exactly the area of its children and the
+// result can cover a bit more or less than cells. uncompact tells by how much.
+pub fn compact(cells: &[HexCell]) -> Vec<HexCell> {
+    let mut current: HashSet<HexCell> = cells.iter().copied().collect();
+    loop {
+        let mut by_parent: HashMap<HexCell, Vec<HexCell>> = HashMap::new();
+        for cell in &current {
+            if let Some(parent) = cell.parent() {
+                by_parent.entry(parent).or_default().push(*cell);
+            }
+        }
+        let mut changed = false;
+        for (parent, present) in by_parent {
+            let children = parent.children();
+            if !children.is_empty() && children.iter().all(|c| present.contains(c)) {
+                for c in children {
+                    current.remove(&c);
+                }
+                current.insert(parent);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut cells: Vec<HexCell> = current.into_iter().collect();
+    cells.sort_unstable();
+    cells
+}
+
+// the cells of res that cells stand for, the inverse of compact where that is exact
+pub fn uncompact(cells: &[HexCell], res: u8) -> Vec<HexCell> {
+    let mut current: Vec<HexCell> = cells.iter().copied().filter(|c| c.res() <= res).collect();
+    let mut out: HashSet<HexCell> = HashSet::new();
+    while let Some(cell) = current.pop() {
+        if cell.res() == res {
+            out.insert(cell);
+        } else {
+            current.extend(cell.children());
+        }
+    }
+    let mut cells: Vec<HexCell> = out.into_iter().collect();
+    cells.sort_unstable();
+    cells
+}
+
+// elements bucketed by the hex cell they fall into
+pub struct HexIndex<T> {
+    res: u8,
+    buckets: HashMap<HexCell, Vec<(T, Point)>>,
+    extent: BBox,
+    len: usize,
+}
+
+impl<T> HexIndex<T> {
+    pub fn with_resolution(data: Vec<(T, Point)>, res: u8) -> Self {
+        let mut index = HexIndex {
+            res: res.min(MAX_RES),
+            buckets: HashMap::new(),
+            extent: extent_of(data.iter().map(|e| &e.1)),
+            len: data.len(),
+        };
+        for e in data {
+            let cell = HexCell::from_point(&e.1, index.res);
+            index.buckets.entry(cell).or_default().push(e);
+        }
+        for bucket in index.buckets.values_mut() {
+            bucket.shrink_to_fit();
+        }
+        index
+    }
+
+    // hex bins with their counts, for aggregation
+    pub fn bins(&self) -> impl Iterator<Item = (HexCell, usize)> + '_ {
+        self.buckets.iter().map(|(cell, b)| (*cell, b.len()))
+    }
+
+    // number of cells of the index resolution needed to cover env, roughly
+    fn estimate_cells(&self, env: &BBox) -> f64 {
+        let size = cell_size(self.res);
+        let area = (env.maxx as f64 - env.minx as f64).to_radians()
+            * ((env.maxy as f64).to_radians().sin() - (env.miny as f64).to_radians().sin());
+        area / (1.5 * 3f64.sqrt() * size * size)
+    }
+}
+
+impl<T> SpatialIndex<T> for HexIndex<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "hex";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let res = auto_resolution(data.len());
+        HexIndex::with_resolution(data, res)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.buckets.capacity() * (std::mem::size_of::<(HexCell, Vec<(T, Point)>)>() + 1)
+            + self
+                .buckets
+                .values()
+                .map(|b| b.capacity() * std::mem::size_of::<(T, Point)>())
+                .sum::<usize>()
+    }
+}
+
+impl<T> VisitQuery<T> for HexIndex<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if env.minx > env.maxx || env.miny > env.maxy {
+            return;
+        }
+        if self.estimate_cells(env) > self.buckets.len() as f64 {
+            // more cells than buckets, scanning everything is cheaper
+            for bucket in self.buckets.values() {
+                counter.nodes(1);
+                counter.entries(bucket.len());
+                bucket
+                    .iter()
+                    .filter(|e| contains(env, &e.1))
+                    .for_each(|e| visit(&e.0));
+            }
+            return;
+        }
+        // a cell lies within its circumradius (an angle, since the projection only stretches)
+        let radius = cell_size(self.res) * (1f64 + 1e-9);
+        for cell in cover_bbox(env, self.res) {
+            let bucket = match self.buckets.get(&cell) {
+                Some(bucket) => bucket,
+                None => continue,
+            };
+            counter.nodes(1);
+            counter.bbox_tests(1);
+            if cap_bound(&cell.center_xyz(), radius).inside(env) {
+                bucket.iter().for_each(|e| visit(&e.0));
+            } else {
+                counter.entries(bucket.len());
+                bucket
+                    .iter()
+                    .filter(|e| contains(env, &e.1))
+                    .for_each(|e| visit(&e.0));
+            }
+        }
+    }
+}
+
+// approximate: grows k-rings until there are k candidates, then takes one more ring
+impl<T> KnnQuery<T> for HexIndex<T>
+where
+    T: Clone,
+{
+    fn query_knn(&self, center: &Point, k: usize, list: &mut Vec<T>) {
+        if k == 0 || self.len == 0 {
+            return;
+        }
+        let origin = HexCell::from_point(center, self.res);
+        let mut seen = HashSet::new();
+        seen.insert(origin);
+        let mut ring = vec![origin];
+        let mut candidates: Vec<(f64, &T)> = Vec::new();
+        let mut extra_ring = false;
+        while !ring.is_empty() {
+            for cell in &ring {
+                if let Some(bucket) = self.buckets.get(cell) {
+                    candidates.extend(bucket.iter().map(|e| (haversine_m(center, &e.1), &e.0)));
+                }
+            }
+            if extra_ring {
+                break;
+            }
+            if candidates.len() >= k || candidates.len() == self.len {
+                extra_ring = true;
+            }
+            let mut next = Vec::new();
+            for cell in &ring {
+                for n in cell.neighbours() {
+                    if seen.insert(n) {
+                        next.push(n);
+                    }
+                }
+            }
+            ring = next;
+        }
+        if candidates.len() < k.min(self.len) {
+            // coarse resolutions can leave cells the rings never reach
+            for (cell, bucket) in &self.buckets {
+                if !seen.contains(cell) {
+                    candidates.extend(bucket.iter().map(|e| (haversine_m(center, &e.1), &e.0)));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        list.extend(candidates.into_iter().take(k).map(|c| c.1.clone()));
+    }
+}
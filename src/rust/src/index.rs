@@ -0,0 +1,379 @@
+use hprtree::{BBox, HPRTree, HPRTreeBuilder, Point};
+use std::cell::Cell;
+
+use rstar::{Envelope, RTree, RTreeObject, SelectionFunction, AABB};
+
+use crate::quality::{rtree_quality, TreeQuality};
+use crate::sphere::{cap_bound, haversine_m, radius_to_angle, to_xyz, EARTH_RADIUS_M};
+use crate::TestSize;
+
+// common interface of all the in-tree backends, modeled after what the hprtree benches use
+pub trait SpatialIndex<T> {
+    // used for the result/ directories and the szfiles
+    const NAME: &'static str;
+
+    fn build(data: Vec<(T, Point)>) -> Self;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn extent(&self) -> BBox;
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>);
+    fn query(&self, env: &BBox) -> Vec<T> {
+        let mut list = Vec::new();
+        self.query_with_list(env, &mut list);
+        list
+    }
+    fn size_in_bytes(&self) -> usize;
+    // for backends that carry a model or similar next to the data, reported separately
+    fn model_size_in_bytes(&self) -> Option<usize> {
+        None
+    }
+    // structure metrics, for the backends that are trees
+    fn quality(&self) -> Option<TreeQuality> {
+        None
+    }
+}
+
+// everything within radius_m meters (great circle) of center
+pub trait RadiusQuery<T> {
+    fn query_radius(&self, center: &Point, radius_m: f64, list: &mut Vec<T>);
+}
+
+// the k elements nearest to center (great circle distance), nearest first
+pub trait KnnQuery<T> {
+    fn query_knn(&self, center: &Point, k: usize, list: &mut Vec<T>);
+}
+
+// work done by envelope queries. what counts as a node depends on the backend (tree node, grid
+// cell, hash bucket, key range), entries are the points tested against the query and bbox_tests
+// the tests of node/cell/region bounds.
+#[derive(Default, Clone)]
+pub struct QueryCounters {
+    pub nodes: usize,
+    pub entries: usize,
+    pub bbox_tests: usize,
+    pub hits: usize,
+}
+
+// the query paths count through this, NoCount compiles away for the timed runs
+pub trait Counter {
+    fn nodes(&mut self, n: usize);
+    fn bbox_tests(&mut self, n: usize);
+    fn entries(&mut self, n: usize);
+}
+
+pub struct NoCount;
+
+impl Counter for NoCount {
+    #[inline(always)]
+    fn nodes(&mut self, _: usize) {}
+    #[inline(always)]
+    fn bbox_tests(&mut self, _: usize) {}
+    #[inline(always)]
+    fn entries(&mut self, _: usize) {}
+}
+
+impl Counter for QueryCounters {
+    fn nodes(&mut self, n: usize) {
+        self.nodes += n;
+    }
+    fn bbox_tests(&mut self, n: usize) {
+        self.bbox_tests += n;
+    }
+    fn entries(&mut self, n: usize) {
+        self.entries += n;
+    }
+}
+
+// envelope queries that hand every hit to a visitor by reference. the result modes below all
+// build on it, query_with_list is the clone mode.
+pub trait VisitQuery<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a;
+
+    fn query_visit<'a, F>(&'a self, env: &BBox, visit: F)
+    where
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        self.visit_with_counter(env, &mut NoCount, visit)
+    }
+    fn query_count(&self, env: &BBox) -> usize {
+        let mut count = 0;
+        self.query_visit(env, |_| count += 1);
+        count
+    }
+    fn query_refs<'a>(&'a self, env: &BBox, list: &mut Vec<&'a T>) {
+        self.query_visit(env, |e| list.push(e))
+    }
+    fn query_ids(&self, env: &BBox, list: &mut Vec<u32>)
+    where
+        T: Identified,
+    {
+        self.query_visit(env, |e| list.push(e.id()))
+    }
+    fn query_counted(&self, env: &BBox, list: &mut Vec<T>, counters: &mut QueryCounters)
+    where
+        T: Clone,
+    {
+        let before = list.len();
+        self.visit_with_counter(env, counters, |e| list.push(e.clone()));
+        counters.hits += list.len() - before;
+    }
+}
+
+// elements that know their own coordinates, for backends that can't hand them out themselves
+pub trait Located {
+    fn location(&self) -> Point;
+}
+
+// elements with an id, for the id only result mode
+pub trait Identified {
+    fn id(&self) -> u32;
+}
+
+impl<T> SpatialIndex<T> for HPRTree<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "hprtree";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let mut treebuilder = HPRTreeBuilder::new(data.len());
+        for e in data {
+            treebuilder.insert(e.0, e.1);
+        }
+        treebuilder.build()
+    }
+    fn len(&self) -> usize {
+        HPRTree::len(self)
+    }
+    fn extent(&self) -> BBox {
+        HPRTree::extent(self)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        HPRTree::query_with_list(self, env, list)
+    }
+    fn size_in_bytes(&self) -> usize {
+        self.current_size_in_bytes()
+    }
+}
+
+// the hprtree only knows boxes, so query the bounding box of the cap and filter by distance
+impl<T> RadiusQuery<T> for HPRTree<T>
+where
+    T: Clone + Located,
+{
+    fn query_radius(&self, center: &Point, radius_m: f64, list: &mut Vec<T>) {
+        let bound = cap_bound(&to_xyz(center), radius_to_angle(radius_m));
+        for env in bound.to_bboxes() {
+            for e in self.query(&env) {
+                if haversine_m(center, &e.location()) <= radius_m {
+                    list.push(e);
+                }
+            }
+        }
+    }
+}
+
+impl<T> KnnQuery<T> for HPRTree<T>
+where
+    T: Clone + Located,
+{
+    fn query_knn(&self, center: &Point, k: usize, list: &mut Vec<T>) {
+        knn_by_radius(self, center, k, list)
+    }
+}
+
+impl<T> SpatialIndex<T> for RTree<T>
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>> + Clone,
+{
+    const NAME: &'static str = "rstar";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        RTree::bulk_load(data.into_iter().map(|e| e.0).collect())
+    }
+    fn len(&self) -> usize {
+        self.size()
+    }
+    fn extent(&self) -> BBox {
+        let env = self.root().envelope();
+        BBox {
+            minx: env.lower()[0],
+            maxx: env.upper()[0],
+            miny: env.lower()[1],
+            maxy: env.upper()[1],
+        }
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        let aabb = AABB::from_corners([env.minx, env.miny], [env.maxx, env.maxy]);
+        list.extend(self.locate_in_envelope(&aabb).cloned());
+    }
+    fn size_in_bytes(&self) -> usize {
+        TestSize::size_in_bytes(self)
+    }
+    fn quality(&self) -> Option<TreeQuality> {
+        Some(rtree_quality(self))
+    }
+}
+
+impl<T> RadiusQuery<T> for RTree<T>
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>> + Clone + Located,
+{
+    fn query_radius(&self, center: &Point, radius_m: f64, list: &mut Vec<T>) {
+        let bound = cap_bound(&to_xyz(center), radius_to_angle(radius_m));
+        for env in bound.to_bboxes() {
+            let aabb = AABB::from_corners([env.minx, env.miny], [env.maxx, env.maxy]);
+            for e in self.locate_in_envelope(&aabb) {
+                if haversine_m(center, &e.location()) <= radius_m {
+                    list.push(e.clone());
+                }
+            }
+        }
+    }
+}
+
+// does what locate_in_envelope's selection function does, counting along the way. the methods
+// only get &self, hence the cells.
+struct CountingSelection {
+    env: AABB<[f32; 2]>,
+    parents: Cell<usize>,
+    parent_tests: Cell<usize>,
+    leaf_tests: Cell<usize>,
+}
+
+impl<T> SelectionFunction<T> for &CountingSelection
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    fn should_unpack_parent(&self, envelope: &AABB<[f32; 2]>) -> bool {
+        self.parent_tests.set(self.parent_tests.get() + 1);
+        let unpack = envelope.intersects(&self.env);
+        if unpack {
+            self.parents.set(self.parents.get() + 1);
+        }
+        unpack
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        self.leaf_tests.set(self.leaf_tests.get() + 1);
+        self.env.contains_envelope(&leaf.envelope())
+    }
+}
+
+impl<T> VisitQuery<T> for RTree<T>
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        let selection = CountingSelection {
+            env: AABB::from_corners([env.minx, env.miny], [env.maxx, env.maxy]),
+            parents: Cell::new(0),
+            parent_tests: Cell::new(0),
+            leaf_tests: Cell::new(0),
+        };
+        self.locate_with_selection_function(&selection)
+            .for_each(visit);
+        counter.nodes(selection.parents.get());
+        counter.bbox_tests(selection.parent_tests.get());
+        counter.entries(selection.leaf_tests.get());
+    }
+}
+
+// not nearest_neighbor_iter, that one measures in degrees
+impl<T> KnnQuery<T> for RTree<T>
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>> + Clone + Located,
+{
+    fn query_knn(&self, center: &Point, k: usize, list: &mut Vec<T>) {
+        knn_by_radius(self, center, k, list)
+    }
+}
+
+// exact knn for backends that only answer radius queries: the radius doubles until the circle
+// holds k elements, the k nearest are then all inside it
+pub fn knn_by_radius<T, I>(index: &I, center: &Point, k: usize, list: &mut Vec<T>)
+where
+    T: Located,
+    I: SpatialIndex<T> + RadiusQuery<T>,
+{
+    let k = k.min(index.len());
+    if k == 0 {
+        return;
+    }
+    let max_radius = std::f64::consts::PI * EARTH_RADIUS_M;
+    // start with the circle that would hold k elements if they were spread evenly over the earth
+    let mut radius =
+        (EARTH_RADIUS_M * (4f64 * k as f64 / index.len() as f64).sqrt()).min(max_radius);
+    let mut found = Vec::new();
+    loop {
+        index.query_radius(center, radius, &mut found);
+        if found.len() >= k || radius >= max_radius {
+            break;
+        }
+        found.clear();
+        radius = (radius * 2f64).min(max_radius);
+    }
+    let mut found: Vec<(f64, T)> = found
+        .into_iter()
+        .map(|e| (haversine_m(center, &e.location()), e))
+        .collect();
+    found.sort_by(|a, b| a.0.total_cmp(&b.0));
+    list.extend(found.into_iter().take(k).map(|e| e.1));
+}
+
+// BBox is a plain struct of four floats, this just copies it field by field
+pub fn clone_bbox(b: &BBox) -> BBox {
+    BBox {
+        minx: b.minx,
+        maxx: b.maxx,
+        miny: b.miny,
+        maxy: b.maxy,
+    }
+}
+
+pub fn contains(env: &BBox, p: &Point) -> bool {
+    p.x >= env.minx && p.x <= env.maxx && p.y >= env.miny && p.y <= env.maxy
+}
+
+pub fn intersects(a: &BBox, b: &BBox) -> bool {
+    a.minx <= b.maxx && a.maxx >= b.minx && a.miny <= b.maxy && a.maxy >= b.miny
+}
+
+// extent of a point set, empty sets get a degenerate box at the origin
+pub fn extent_of<'a, I>(points: I) -> BBox
+where
+    I: IntoIterator<Item = &'a Point>,
+{
+    let mut env = BBox {
+        minx: f32::INFINITY,
+        maxx: f32::NEG_INFINITY,
+        miny: f32::INFINITY,
+        maxy: f32::NEG_INFINITY,
+    };
+    for p in points {
+        env.minx = env.minx.min(p.x);
+        env.maxx = env.maxx.max(p.x);
+        env.miny = env.miny.min(p.y);
+        env.maxy = env.maxy.max(p.y);
+    }
+    if env.minx > env.maxx {
+        env = BBox {
+            minx: 0f32,
+            maxx: 0f32,
+            miny: 0f32,
+            maxy: 0f32,
+        };
+    }
+    env
+}
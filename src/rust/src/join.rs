@@ -0,0 +1,152 @@
+use hprtree::{BBox, Point};
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, RTreeObject, AABB};
+
+use crate::index::{contains, Located, SpatialIndex, VisitQuery};
+use crate::sphere::{cap_bound, haversine_m, radius_to_angle, to_xyz};
+
+// which points of b pair up with a point of a
+#[derive(Clone, Copy)]
+pub enum JoinPredicate {
+    // inside the box reaching the given degrees out from the point in every direction
+    Box(f32),
+    // great circle distance up to the given meters
+    Distance(f64),
+}
+
+impl JoinPredicate {
+    pub fn name(&self) -> String {
+        match self {
+            JoinPredicate::Box(d) => format!("box{d}"),
+            JoinPredicate::Distance(m) => format!("dist{m}"),
+        }
+    }
+
+    // boxes holding every point that can pair up with p, two if crossing the antimeridian
+    pub fn windows(&self, p: &Point) -> Vec<BBox> {
+        match *self {
+            JoinPredicate::Box(d) => vec![BBox {
+                minx: p.x - d,
+                maxx: p.x + d,
+                miny: p.y - d,
+                maxy: p.y + d,
+            }],
+            JoinPredicate::Distance(m) => cap_bound(&to_xyz(p), radius_to_angle(m)).to_bboxes(),
+        }
+    }
+
+    // whether a point in one of the windows of a pairs up with it
+    fn refine(&self, a: &Point, b: &Point) -> bool {
+        match *self {
+            JoinPredicate::Box(_) => true,
+            JoinPredicate::Distance(m) => haversine_m(a, b) <= m,
+        }
+    }
+}
+
+// one window query per point of a against an index over b
+pub fn index_nested_loop<TA, TB, I, F>(a: &[(TA, Point)], b: &I, pred: JoinPredicate, mut f: F)
+where
+    TB: Located,
+    I: VisitQuery<TB>,
+    F: FnMut(&TA, &TB),
+{
+    for (ea, pa) in a {
+        for window in pred.windows(pa) {
+            b.query_visit(&window, |eb| {
+                if pred.refine(pa, &eb.location()) {
+                    f(ea, eb);
+                }
+            });
+        }
+    }
+}
+
+// the same for backends that only hand out clones, like hprtree
+pub fn index_nested_loop_owned<TA, TB, I, F>(
+    a: &[(TA, Point)],
+    b: &I,
+    pred: JoinPredicate,
+    mut f: F,
+) where
+    TB: Located,
+    I: SpatialIndex<TB>,
+    F: FnMut(&TA, &TB),
+{
+    let mut list = Vec::new();
+    for (ea, pa) in a {
+        for window in pred.windows(pa) {
+            list.clear();
+            b.query_with_list(&window, &mut list);
+            for eb in &list {
+                if pred.refine(pa, &eb.location()) {
+                    f(ea, eb);
+                }
+            }
+        }
+    }
+}
+
+// synchronized traversal of an rtree over the windows of a and the one over b. the window tree
+// is part of the join, so building it counts.
+pub fn rtree_join<TA, TB, F>(a: &[(TA, Point)], b: &RTree<TB>, pred: JoinPredicate, mut f: F)
+where
+    TB: RTreeObject<Envelope = AABB<[f32; 2]>> + Located,
+    F: FnMut(&TA, &TB),
+{
+    let windows: Vec<GeomWithData<Rectangle<[f32; 2]>, usize>> = a
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (_, p))| {
+            pred.windows(p).into_iter().map(move |w| {
+                GeomWithData::new(
+                    Rectangle::from_corners([w.minx, w.miny], [w.maxx, w.maxy]),
+                    i,
+                )
+            })
+        })
+        .collect();
+    let windows = RTree::bulk_load(windows);
+    for (window, eb) in windows.intersection_candidates_with_other_tree(b) {
+        let (ea, pa) = &a[window.data];
+        if pred.refine(pa, &eb.location()) {
+            f(ea, eb);
+        }
+    }
+}
+
+// no index at all: the windows sorted by their left edge sweep over the points of b sorted by x
+pub fn plane_sweep<TA, TB, F>(a: &[(TA, Point)], b: &[(TB, Point)], pred: JoinPredicate, mut f: F)
+where
+    F: FnMut(&TA, &TB),
+{
+    let mut windows: Vec<(BBox, usize)> = a
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (_, p))| pred.windows(p).into_iter().map(move |w| (w, i)))
+        .collect();
+    windows.sort_by(|x, y| x.0.minx.total_cmp(&y.0.minx));
+    // coordinates next to the index so the sweep doesn't jump around in b
+    let mut points: Vec<(Point, usize)> = b
+        .iter()
+        .enumerate()
+        .map(|(i, (_, p))| (Point { x: p.x, y: p.y }, i))
+        .collect();
+    points.sort_by(|x, y| x.0.x.total_cmp(&y.0.x));
+
+    let mut start = 0;
+    for (window, i) in &windows {
+        while start < points.len() && points[start].0.x < window.minx {
+            start += 1;
+        }
+        let (ea, pa) = &a[*i];
+        for (pb, j) in &points[start..] {
+            if pb.x > window.maxx {
+                break;
+            }
+            if contains(window, pb) && pred.refine(pa, pb) {
+                f(ea, &b[*j].0);
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::marker::PhantomData;
+
+use hprtree::{BBox, Point};
+
+use crate::index::{
+    clone_bbox, contains, extent_of, intersects, Counter, SpatialIndex, VisitQuery,
+};
+
+// bits per dimension of the quantized coordinates, f32 has no more than that anyway
+const KEY_BITS: u32 = 24;
+// average number of keys per second stage model
+const LEAF_MODEL_TARGET: usize = 1024;
+
+// space filling curve used to turn the quantized coordinates into one sortable key
+pub trait Curve {
+    const INDEX_NAME: &'static str;
+    fn key(x: u32, y: u32) -> u64;
+}
+
+pub struct Morton;
+pub struct Hilbert;
+
+// spreads the lower 32 bits of v over the even bits of the result
+fn spread(v: u32) -> u64 {
+    let mut v = v as u64;
+    v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+    v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+    v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+    v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+    v
+}
+
+impl Curve for Morton {
+    const INDEX_NAME: &'static str = "learned_morton";
+    fn key(x: u32, y: u32) -> u64 {
+        spread(x) | (spread(y) << 1)
+    }
+}
+
+// position of (x, y) along the hilbert curve filling the 2^bits x 2^bits square
+pub fn hilbert(x: u64, y: u64, bits: u32) -> u64 {
+    let n = 1u64 << bits;
+    let (mut x, mut y) = (x, y);
+    let mut d = 0u64;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = ((x & s) > 0) as u64;
+        let ry = ((y & s) > 0) as u64;
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+impl Curve for Hilbert {
+    const INDEX_NAME: &'static str = "learned_hilbert";
+    fn key(x: u32, y: u32) -> u64 {
+        hilbert(x as u64, y as u64, KEY_BITS)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LinearModel {
+    slope: f64,
+    intercept: f64,
+}
+
+impl LinearModel {
+    // least squares fit of position over key
+    fn fit(keys: &[u64], first_pos: usize) -> Self {
+        let n = keys.len() as f64;
+        if keys.len() < 2 || keys[0] == keys[keys.len() - 1] {
+            return LinearModel {
+                slope: 0f64,
+                intercept: first_pos as f64,
+            };
+        }
+        let base = keys[0] as f64;
+        let mean_k = keys.iter().map(|&k| k as f64 - base).sum::<f64>() / n;
+        let mean_p = first_pos as f64 + (n - 1f64) / 2f64;
+        let mut cov = 0f64;
+        let mut var = 0f64;
+        for (i, &k) in keys.iter().enumerate() {
+            let dk = k as f64 - base - mean_k;
+            cov += dk * ((first_pos + i) as f64 - mean_p);
+            var += dk * dk;
+        }
+        let slope = cov / var;
+        LinearModel {
+            slope,
+            intercept: mean_p - slope * (mean_k + base),
+        }
+    }
+
+    fn predict(&self, key: u64) -> f64 {
+        self.slope * key as f64 + self.intercept
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LeafModel {
+    model: LinearModel,
+    err_lo: usize,
+    err_hi: usize,
+}
+
+// two stage recursive model index over the sorted keys, every leaf knows its max prediction error
+struct Rmi {
+    root: LinearModel,
+    leaves: Vec<LeafModel>,
+    m: usize,
+    n: usize,
+}
+
+impl Rmi {
+    fn train(keys: &[u64]) -> Self {
+        let n = keys.len();
+        let m = (n / LEAF_MODEL_TARGET).max(1);
+        let mut rmi = Rmi {
+            root: LinearModel::fit(keys, 0),
+            leaves: Vec::with_capacity(m),
+            m,
+            n,
+        };
+        // the root is monotonic, so every leaf gets a contiguous run of keys
+        let mut from = 0;
+        for leaf in 0..m {
+            let to = if leaf == m - 1 {
+                n
+            } else {
+                from + keys[from..].partition_point(|&k| rmi.route(k) <= leaf)
+            };
+            let model = if from < to {
+                LinearModel::fit(&keys[from..to], from)
+            } else {
+                LinearModel {
+                    slope: 0f64,
+                    intercept: from as f64,
+                }
+            };
+            let mut err_lo = 0;
+            let mut err_hi = 0;
+            for (i, &k) in keys[from..to].iter().enumerate() {
+                let pred = model.predict(k);
+                let pos = (from + i) as f64;
+                if pred > pos {
+                    err_lo = err_lo.max((pred - pos).ceil() as usize);
+                } else {
+                    err_hi = err_hi.max((pos - pred).ceil() as usize);
+                }
+            }
+            rmi.leaves.push(LeafModel {
+                model,
+                err_lo,
+                err_hi,
+            });
+            from = to;
+        }
+        rmi
+    }
+
+    fn route(&self, key: u64) -> usize {
+        let pos = self.root.predict(key) * self.m as f64 / self.n.max(1) as f64;
+        (pos.max(0f64) as usize).min(self.m - 1)
+    }
+
+    // first position whose key is >= key, searching only within the error bounds of the leaf
+    fn lower_bound(&self, keys: &[u64], key: u64) -> usize {
+        let leaf = &self.leaves[self.route(key)];
+        let pred = (leaf.model.predict(key).max(0f64) as usize).min(self.n);
+        let mut lo = pred.saturating_sub(leaf.err_lo + 1);
+        let mut hi = (pred + leaf.err_hi + 1).min(self.n);
+        // keys that were not trained on can fall outside the bounds, widen exponentially then
+        let mut step = 1;
+        while lo > 0 && keys[lo - 1] >= key {
+            lo = lo.saturating_sub(step);
+            step *= 2;
+        }
+        step = 1;
+        while hi < self.n && keys[hi] < key {
+            hi = (hi + step).min(self.n);
+            step *= 2;
+        }
+        lo + keys[lo..hi].partition_point(|&k| k < key)
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self) + self.leaves.capacity() * std::mem::size_of::<LeafModel>()
+    }
+}
+
+// points sorted along a space filling curve, positions are looked up through a learned model
+pub struct LearnedIndex<T, C> {
+    keys: Vec<u64>,
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    elements: Vec<T>,
+    extent: BBox,
+    rmi: Rmi,
+    curve: PhantomData<C>,
+}
+
+impl<T, C> LearnedIndex<T, C>
+where
+    C: Curve,
+{
+    fn quantize(v: f32, min: f32, max: f32) -> u32 {
+        if max <= min {
+            return 0;
+        }
+        let cells = ((1u64 << KEY_BITS) - 1) as f64;
+        let q = (v as f64 - min as f64) / (max as f64 - min as f64) * cells;
+        (q.max(0f64) as u64).min(cells as u64) as u32
+    }
+
+    fn qx(&self, x: f32) -> u32 {
+        Self::quantize(x, self.extent.minx, self.extent.maxx)
+    }
+
+    fn qy(&self, y: f32) -> u32 {
+        Self::quantize(y, self.extent.miny, self.extent.maxy)
+    }
+
+    // key intervals covering the quantized query rectangle [qx0, qx1] x [qy0, qy1]
+    fn decompose<K: Counter>(&self, q: [u32; 4], counter: &mut K) -> Vec<(u64, u64)> {
+        let side = (q[1] - q[0]).max(q[3] - q[2]) as u64 + 1;
+        // stop refining once cells are about a quarter of the query
+        let max_level = (KEY_BITS + 2)
+            .saturating_sub(63 - side.leading_zeros())
+            .min(KEY_BITS);
+        let mut intervals = Vec::new();
+        Self::decompose_cell(0, 0, 0, &q, max_level, &mut intervals, counter);
+        intervals.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+        for (lo, hi) in intervals {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        merged
+    }
+
+    fn decompose_cell<K: Counter>(
+        level: u32,
+        cx: u32,
+        cy: u32,
+        q: &[u32; 4],
+        max_level: u32,
+        out: &mut Vec<(u64, u64)>,
+        counter: &mut K,
+    ) {
+        counter.bbox_tests(1);
+        let shift = KEY_BITS - level;
+        let (x0, y0) = ((cx as u64) << shift, (cy as u64) << shift);
+        let (x1, y1) = (x0 + (1u64 << shift) - 1, y0 + (1u64 << shift) - 1);
+        let (qx0, qx1, qy0, qy1) = (q[0] as u64, q[1] as u64, q[2] as u64, q[3] as u64);
+        if x1 < qx0 || x0 > qx1 || y1 < qy0 || y0 > qy1 {
+            return;
+        }
+        let inside = x0 >= qx0 && x1 <= qx1 && y0 >= qy0 && y1 <= qy1;
+        if inside || level == max_level {
+            // every quadtree cell is one aligned block of keys, for both curves
+            let mask = (1u64 << (2 * shift)) - 1;
+            let start = C::key(x0 as u32, y0 as u32) & !mask;
+            out.push((start, start | mask));
+            return;
+        }
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            Self::decompose_cell(
+                level + 1,
+                cx * 2 + dx,
+                cy * 2 + dy,
+                q,
+                max_level,
+                out,
+                counter,
+            );
+        }
+    }
+}
+
+impl<T, C> SpatialIndex<T> for LearnedIndex<T, C>
+where
+    T: Clone,
+    C: Curve,
+{
+    const NAME: &'static str = C::INDEX_NAME;
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let mut keyed: Vec<(u64, T, Point)> = data
+            .into_iter()
+            .map(|(e, p)| {
+                let x = Self::quantize(p.x, extent.minx, extent.maxx);
+                let y = Self::quantize(p.y, extent.miny, extent.maxy);
+                (C::key(x, y), e, p)
+            })
+            .collect();
+        keyed.sort_by_key(|e| e.0);
+
+        let mut keys = Vec::with_capacity(keyed.len());
+        let mut xs = Vec::with_capacity(keyed.len());
+        let mut ys = Vec::with_capacity(keyed.len());
+        let mut elements = Vec::with_capacity(keyed.len());
+        for (k, e, p) in keyed {
+            keys.push(k);
+            xs.push(p.x);
+            ys.push(p.y);
+            elements.push(e);
+        }
+        let rmi = Rmi::train(&keys);
+        LearnedIndex {
+            keys,
+            xs,
+            ys,
+            elements,
+            extent,
+            rmi,
+            curve: PhantomData,
+        }
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.keys.capacity() * std::mem::size_of::<u64>()
+            + (self.xs.capacity() + self.ys.capacity()) * std::mem::size_of::<f32>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+            + self.rmi.size_in_bytes()
+            - std::mem::size_of::<Rmi>()
+    }
+    fn model_size_in_bytes(&self) -> Option<usize> {
+        Some(self.rmi.size_in_bytes())
+    }
+}
+
+impl<T, C> VisitQuery<T> for LearnedIndex<T, C>
+where
+    C: Curve,
+{
+    fn visit_with_counter<'a, K, F>(&'a self, env: &BBox, counter: &mut K, mut visit: F)
+    where
+        K: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if env.minx > env.maxx || env.miny > env.maxy || !intersects(env, &self.extent) {
+            return;
+        }
+        let q = [
+            self.qx(env.minx),
+            self.qx(env.maxx),
+            self.qy(env.miny),
+            self.qy(env.maxy),
+        ];
+        for (lo, hi) in self.decompose(q, counter) {
+            counter.nodes(1);
+            let mut i = self.rmi.lower_bound(&self.keys, lo);
+            while i < self.keys.len() && self.keys[i] <= hi {
+                counter.entries(1);
+                let p = Point {
+                    x: self.xs[i],
+                    y: self.ys[i],
+                };
+                if contains(env, &p) {
+                    visit(&self.elements[i]);
+                }
+                i += 1;
+            }
+        }
+    }
+}
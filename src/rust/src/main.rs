@@ -1,6 +1,8 @@
 // #![feature(offset_of)]
 use std::{
+    collections::HashMap,
     fs::{self, create_dir_all, File, OpenOptions},
+    hint::black_box,
     io::{self, stdout, Read, Write},
     ops::Shl,
     path::Path,
@@ -12,8 +14,53 @@ use csv::StringRecord;
 use hprtree::{BBox, HPRTree, HPRTreeBuilder, Point};
 use rstar::{ParentNode, RTree, RTreeObject, AABB};
 
+mod aggregate;
+mod balltree;
+mod compressed;
+mod filtered;
+mod geocode;
+mod geohash;
+mod grid;
+mod hex;
+mod index;
+mod join;
+mod learned;
+mod matching;
+mod packed;
+mod quality;
+mod querylog;
+mod s2;
+mod scan;
+mod slab;
+mod sphere;
+mod topk;
+mod workload;
+
+use aggregate::{Aggregate, AggregateTree};
+use balltree::BallTree;
+use compressed::CompressedTree;
+use filtered::{AttrFilter, Attributes, FilteredQuery, Partitioned, PostFilter, SummaryTree};
+use geohash::GeohashIndex;
+use grid::{GridResolution, UniformGrid};
+use hex::HexIndex;
+use index::{Identified, KnnQuery, Located, QueryCounters, RadiusQuery, SpatialIndex, VisitQuery};
+use join::JoinPredicate;
+use learned::{Hilbert, LearnedIndex, Morton};
+use matching::{City, Match};
+use packed::{Bfs, Dfs, PackedTree, Veb};
+use querylog::{LoggedQuery, Pace, Replay};
+use s2::S2Index;
+use scan::{LinearScan, SortedArray};
+use slab::{SlabTree, F32, Q16, Q32};
+use topk::{RankKey, TopKTree};
+use workload::{Centres, Hotspot};
+
 const ENV_SIZES: [usize; 5] = [16, 64, 256, 1024, 4096];
 const ENV_COUNT: usize = 16;
+// the selectivity targets of genenvelopes --targets 0.001%,0.1%,1% as file suffixes, these files
+// carry the count of every envelope. envelope_variants also finds them generated with
+// --tolerance, which datasets with many duplicate points need.
+const ENV_FRACTIONS: [&str; 3] = ["0.001pct", "0.1pct", "1pct"];
 
 const BUILD_COUNT_LIMIT: usize = 5_000_000;
 const BUILD_TIME_LIMIT: Duration = Duration::from_secs(30);
@@ -24,6 +71,17 @@ const QUERYALL_TIME_LIMIT: Duration = Duration::from_secs(30);
 const QUERYPRE_LIMIT: usize = 5_000_000;
 const QUERYPRE_TIME_LIMIT: Duration = Duration::from_secs(30);
 
+const JOIN_LIMIT: usize = 1_000;
+const JOIN_TIME_LIMIT: Duration = Duration::from_secs(30);
+
+// k of the top-k queries
+const TOPK_SIZES: [usize; 3] = [1, 10, 100];
+
+// queries per replayed stream, there is one stream per workload and querypre size
+const REPLAY_LEN: usize = 200_000;
+const REPLAY_SEED: u64 = 20230601;
+const REPLAY_PERCENTILES: [f64; 5] = [0.5, 0.9, 0.99, 0.999, 1.0];
+
 #[derive(Clone, Debug)]
 struct Element {
     pub lat: f32,
@@ -38,6 +96,21 @@ impl RTreeObject for Element {
     }
 }
 
+impl Located for Element {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl Identified for Element {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
 #[derive(Clone, Debug)]
 struct BiggerElement {
     pub lat: f32,
@@ -54,6 +127,21 @@ impl RTreeObject for BiggerElement {
     }
 }
 
+impl Located for BiggerElement {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl Identified for BiggerElement {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
 #[derive(Clone, Debug)]
 struct BigElement {
     pub lat: f32,
@@ -68,6 +156,21 @@ impl RTreeObject for BigElement {
     }
 }
 
+impl Located for BigElement {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl Identified for BigElement {
+    fn id(&self) -> u32 {
+        self.data[0] as u32
+    }
+}
+
 #[derive(Clone, Debug)]
 struct VeryBigElement {
     pub lat: f32,
@@ -82,6 +185,21 @@ impl RTreeObject for VeryBigElement {
     }
 }
 
+impl Located for VeryBigElement {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl Identified for VeryBigElement {
+    fn id(&self) -> u32 {
+        self.data[0] as u32
+    }
+}
+
 #[derive(Clone, Debug)]
 struct VeryVeryBigElement {
     pub lat: f32,
@@ -96,6 +214,116 @@ impl RTreeObject for VeryVeryBigElement {
     }
 }
 
+impl Located for VeryVeryBigElement {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl Identified for VeryVeryBigElement {
+    fn id(&self) -> u32 {
+        self.data[0] as u32
+    }
+}
+
+// the real columns of the sources instead of filler, so clones and drops touch the heap.
+// sources without a column leave it empty.
+#[derive(Clone, Debug)]
+struct CityElement {
+    pub lat: f32,
+    pub lon: f32,
+    pub id: u32,
+    pub name: String,
+    pub ascii_name: String,
+    pub country: String,
+    pub admin1: String,
+    pub admin2: String,
+    pub population: u64,
+    pub timezone: String,
+    pub elevation: Option<i32>,
+}
+
+impl CityElement {
+    // heap bytes of the strings, on top of the size_of the backends count
+    fn heap_size(&self) -> usize {
+        self.name.capacity()
+            + self.ascii_name.capacity()
+            + self.country.capacity()
+            + self.admin1.capacity()
+            + self.admin2.capacity()
+            + self.timezone.capacity()
+    }
+
+    // synthetic and random points only have an id to go by
+    fn generated(lat: f32, lon: f32, id: u32) -> Self {
+        CityElement {
+            lat,
+            lon,
+            id,
+            name: format!("place {id}"),
+            ascii_name: format!("place {id}"),
+            country: String::new(),
+            admin1: String::new(),
+            admin2: String::new(),
+            population: 0,
+            timezone: String::new(),
+            elevation: None,
+        }
+    }
+}
+
+impl RTreeObject for CityElement {
+    type Envelope = AABB<[f32; 2]>;
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_point([self.lon, self.lat])
+    }
+}
+
+impl Located for CityElement {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+impl Identified for CityElement {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+// the numeric columns top-k queries rank cities by
+struct ByPopulation;
+struct ByElevation;
+
+impl RankKey<CityElement> for ByPopulation {
+    const NAME: &'static str = "population";
+    fn rank(e: &CityElement) -> Option<i64> {
+        Some(e.population as i64)
+    }
+}
+
+impl RankKey<CityElement> for ByElevation {
+    const NAME: &'static str = "elevation";
+    fn rank(e: &CityElement) -> Option<i64> {
+        e.elevation.map(|m| m as i64)
+    }
+}
+
+impl Attributes for CityElement {
+    fn population(&self) -> u64 {
+        self.population
+    }
+    fn country(&self) -> &str {
+        &self.country
+    }
+}
+
 fn read<T>(
     delimiter: u8,
     path: &str,
@@ -195,7 +423,7 @@ fn bench_build_hprtree<T: Clone>(data: Vec<(T, Point)>, name: &str) -> HPRTree<T
 
 fn bench_build_rstar<T: Clone>(data: Vec<T>, name: &str) -> RTree<T>
 where
-    T: RTreeObject,
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
 {
     let stime = time::Instant::now();
 
@@ -210,7 +438,7 @@ where
         let end = time::Instant::now();
         let diff = end - start;
         total += diff;
-        size = tree.size_in_bytes();
+        size = TestSize::size_in_bytes(&tree);
         assert!(tree.size() != 0);
         timings.push(diff);
         {
@@ -254,7 +482,12 @@ where
     let etime = time::Instant::now();
     println!("{name} done in {:?} ({total:?})", etime - stime);
 
-    RTree::bulk_load(data.clone())
+    let tree = RTree::bulk_load(data.clone());
+    append_szfile(
+        "rstar_quality",
+        &format!("{name}: {}", quality::rtree_quality(&tree)),
+    );
+    tree
 }
 
 fn bench_queryall_hprtree<T>(filename: String, tree: &HPRTree<T>)
@@ -347,6 +580,7 @@ where
         for (i, size) in ENV_SIZES.iter().enumerate() {
             let mut reader = csv::ReaderBuilder::new()
                 .has_headers(false)
+                .comment(Some(b'#'))
                 .delimiter(b',')
                 .from_path(format!("{}.{}", filename, size))
                 .unwrap();
@@ -443,6 +677,7 @@ where
         for (i, size) in ENV_SIZES.iter().enumerate() {
             let mut reader = csv::ReaderBuilder::new()
                 .has_headers(false)
+                .comment(Some(b'#'))
                 .delimiter(b',')
                 .from_path(format!("{}.{}", filename, size))
                 .unwrap();
@@ -521,142 +756,1843 @@ where
     println!("querypre done in {:?} ({total:?})", etime - stime);
 }
 
-trait TestSize {
-    fn size_in_bytes(&self) -> usize;
+fn write_timings(path: &str, timings: &[Duration]) {
+    let mut file = File::create(path).unwrap();
+    for t in timings {
+        file.write_all((t.as_nanos().to_string() + "\n").as_bytes())
+            .unwrap();
+    }
 }
 
-fn size_in_bytes_helper<T>(node: &ParentNode<T>) -> usize
-where
-    T: RTreeObject,
-{
-    let mut sum = 0;
-    for child in node.children() {
-        sum += std::mem::size_of_val(child);
-        match child {
-            rstar::RTreeNode::Leaf(_) => (),
-            rstar::RTreeNode::Parent(subparent) => sum += size_in_bytes_helper(subparent),
-        }
+fn append_szfile(backend: &str, line: &str) {
+    let mut szfile = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(format!("result/szfiles/{backend}"))
+        .unwrap();
+    szfile.write_all(format!("{line}\n").as_bytes()).unwrap();
+}
+
+fn create_result_dirs(backend: &str) {
+    create_dir_all(Path::new(&format!("result/querypre/{backend}/"))).unwrap();
+    create_dir_all(Path::new(&format!("result/querycount/{backend}/"))).unwrap();
+    create_dir_all(Path::new(&format!("result/queryselectivity/{backend}/"))).unwrap();
+    for mode in ResultMode::ALL {
+        create_dir_all(Path::new(&format!(
+            "result/querymodes/{backend}/{}/",
+            mode.name()
+        )))
+        .unwrap();
     }
-    sum
+    create_dir_all(Path::new(&format!("result/queryall/{backend}/"))).unwrap();
+    create_dir_all(Path::new(&format!("result/build/{backend}/"))).unwrap();
+    create_dir_all(Path::new(&format!("result/build/d_{backend}/"))).unwrap();
 }
 
-impl<T> TestSize for RTree<T>
-where
-    T: RTreeObject,
-{
-    fn size_in_bytes(&self) -> usize {
-        let internal_sz = size_in_bytes_helper(self.root());
-        std::mem::size_of_val(self) + internal_sz
+// loads the ENV_COUNT envelopes of every ENV_SIZES target for the env file `filename`
+fn load_envelopes(filename: &str) -> Vec<Vec<BBox>> {
+    let mut bboxes = Vec::with_capacity(ENV_SIZES.len());
+    for size in ENV_SIZES {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .comment(Some(b'#'))
+            .delimiter(b',')
+            .from_path(format!("{}.{}", filename, size))
+            .unwrap();
+
+        let mut envs = Vec::with_capacity(ENV_COUNT);
+        for result in reader.records() {
+            let result = result.unwrap();
+            envs.push(BBox {
+                minx: result.get(0).unwrap().parse::<f32>().unwrap(),
+                maxx: result.get(1).unwrap().parse::<f32>().unwrap(),
+                miny: result.get(2).unwrap().parse::<f32>().unwrap(),
+                maxy: result.get(3).unwrap().parse::<f32>().unwrap(),
+            });
+        }
+        bboxes.push(envs);
     }
+    bboxes
 }
 
-fn bench_hprtree_opendata<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
-where
-    T: Clone,
-{
-    println!("starting build...");
-    let count = 140974;
-    let tn = &std::any::type_name::<T>()[6..];
+// the envelopes of the env file `filename` with the target `suffix` and the count genenvelopes
+// wrote next to each, None if that target wasn't generated
+fn load_counted_envelopes(filename: &str, suffix: &str) -> Option<Vec<(BBox, usize)>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .comment(Some(b'#'))
+        .delimiter(b',')
+        .from_path(format!("{}.{}", filename, suffix))
+        .ok()?;
 
-    let data = read(
-        b';',
-        "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
-        count,
-        deser,
-    );
+    let mut envs = Vec::with_capacity(ENV_COUNT);
+    for result in reader.records() {
+        let result = result.unwrap();
+        envs.push((
+            BBox {
+                minx: result.get(0).unwrap().parse::<f32>().unwrap(),
+                maxx: result.get(1).unwrap().parse::<f32>().unwrap(),
+                miny: result.get(2).unwrap().parse::<f32>().unwrap(),
+                maxy: result.get(3).unwrap().parse::<f32>().unwrap(),
+            },
+            result.get(4).unwrap().parse::<usize>().unwrap(),
+        ));
+    }
+    Some(envs)
+}
 
-    let tree = bench_build_hprtree(data, &format!("bench_build_hprtree_opendata_{tn}"));
-    bench_queryall_hprtree(format!("bench_queryall_hprtree_opendata_{tn}"), &tree);
-    bench_querypre_hprtree(
-        "../../data/envelopes/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv"
-            .to_string(),
-        &tree,
-    );
+// the suffixes of the envelope files of the env file `filename` with the target `target`: the
+// plain one, those generated with a tolerance, like "0.1pctpm5pct", and those tagged with a
+// shape other than the default, like "0.1pct-square-uniform". sorted, so the plain one comes
+// first.
+fn envelope_variants(filename: &str, target: &str) -> Vec<String> {
+    let path = Path::new(filename);
+    let prefix = format!("{}.", path.file_name().unwrap().to_str().unwrap());
+    let mut suffixes: Vec<String> = match fs::read_dir(path.parent().unwrap()) {
+        Ok(entries) => entries
+            .map(|e| e.unwrap().file_name().to_str().unwrap().to_string())
+            .filter_map(|name| Some(name.strip_prefix(&prefix)?.to_string()))
+            .filter(|suffix| match suffix.strip_prefix(target) {
+                Some(rest) => rest.is_empty() || rest.starts_with("pm") || rest.starts_with('-'),
+                None => false,
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    suffixes.sort();
+    suffixes
 }
 
-fn bench_rstar_opendata<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+// the "{pname}_{fname}" part of the querypre result files
+fn envelope_set_name(filename: &str) -> String {
+    let path = Path::new(filename);
+    let pname = path
+        .parent()
+        .unwrap()
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let fname = path.file_name().unwrap().to_str().unwrap();
+    let fname = fname.strip_suffix(".csv").unwrap_or(fname);
+    format!("{pname}_{fname}")
+}
+
+fn bench_build_index<T, I>(data: Vec<(T, Point)>, name: &str) -> I
 where
     T: Clone,
-    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+    I: SpatialIndex<T>,
 {
-    println!("starting build...");
-    let tn = &std::any::type_name::<T>()[6..];
-    let count = 140974;
-
-    let data = read(
-        b';',
-        "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
-        count,
-        deser,
-    )
-    .into_iter()
-    .map(|e| e.0)
-    .collect();
-
-    let tree = bench_build_rstar(data, &format!("bench_build_rstar_opendata_{tn}"));
-    bench_queryall_rtree(format!("bench_queryall_rstar_opendata_{tn}"), &tree);
-    bench_querypre_rstar(
-        "../../data/envelopes/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv"
-            .to_string(),
-        &tree,
-    );
+    bench_build_index_with(data, name, I::build)
 }
 
-fn bench_hprtree_random_uniform<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+fn bench_build_index_with<T, I, F>(data: Vec<(T, Point)>, name: &str, build: F) -> I
 where
     T: Clone,
+    I: SpatialIndex<T>,
+    F: Fn(Vec<(T, Point)>) -> I,
 {
-    let tn = &std::any::type_name::<T>()[6..];
-    let paths = fs::read_dir("../../data/new/uniform/").unwrap();
-    for path in paths {
-        let path = path.unwrap();
-        if !path.metadata().unwrap().is_file() {
-            continue;
+    let stime = time::Instant::now();
+
+    let mut size = 0;
+    let mut model_size = None;
+    let mut timings = Vec::with_capacity(BUILD_COUNT_LIMIT);
+    let mut d_timings = Vec::with_capacity(BUILD_COUNT_LIMIT);
+    let mut total = Duration::ZERO;
+    for c in 0..BUILD_COUNT_LIMIT {
+        let data = data.clone();
+        let start = time::Instant::now();
+        let index = build(data);
+        let end = time::Instant::now();
+        let diff = end - start;
+        total += diff;
+        size = index.size_in_bytes();
+        model_size = index.model_size_in_bytes();
+        assert!(!index.is_empty());
+        timings.push(diff);
+        {
+            let d_start = time::Instant::now();
+            drop(index);
+            let d_end = time::Instant::now();
+            let d_diff = d_end - d_start;
+            total += d_diff;
+            d_timings.push(d_diff);
         }
-        let filename = path.file_name();
-        let filename_str = filename.to_str().unwrap();
-        let filename_wo_ext = &filename_str[..(filename_str.len() - 4)];
+        if total > BUILD_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    let backend = I::NAME;
+    if let Some(model_size) = model_size {
+        // model size next to the median build time in ns
+        let mut sorted = timings.clone();
+        sorted.sort();
+        let median = sorted[sorted.len() / 2].as_nanos();
+        append_szfile(
+            &format!("{backend}_model"),
+            &format!("{name}: {model_size} {median}"),
+        );
+    }
+    write_timings(&format!("result/build/{backend}/{name}"), &timings);
+    write_timings(&format!("result/build/d_{backend}/{name}"), &d_timings);
+    append_szfile(backend, &format!("{name}: {size}"));
 
-        let count = filename_wo_ext
-            .split_once('_')
-            .unwrap()
-            .0
-            .parse::<usize>()
-            .unwrap();
-        let p = path.path();
-        let pathstr = p.to_str().unwrap();
-        let data = read(b',', pathstr, count, deser);
+    let etime = time::Instant::now();
+    println!("{name} done in {:?} ({total:?})", etime - stime);
 
-        let tree = bench_build_hprtree(
-            data,
-            &format!("bench_build_hprtree_uniform_{filename_wo_ext}_{tn}",),
-        );
-        bench_queryall_hprtree(
-            format!("bench_queryall_hprtree_uniform_{filename_wo_ext}_{tn}"),
-            &tree,
-        );
-        bench_querypre_hprtree(pathstr.replace("/data", "/data/envelopes"), &tree);
+    let index = build(data);
+    if let Some(quality) = index.quality() {
+        // depth overlap deadspace avgfill minfill, then nodes/leaves/entries per level
+        append_szfile(&format!("{backend}_quality"), &format!("{name}: {quality}"));
     }
+    index
 }
 
-fn bench_rstar_random_uniform<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+fn bench_queryall_index<T, I>(filename: String, index: &I)
 where
-    T: Clone,
-    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+    I: SpatialIndex<T>,
 {
-    let tn = &std::any::type_name::<T>()[6..];
-    let paths = fs::read_dir("../../data/new/uniform/").unwrap();
-    for path in paths {
-        let path = path.unwrap();
-        if !path.metadata().unwrap().is_file() {
-            continue;
+    let stime = time::Instant::now();
+
+    let mut timings = Vec::with_capacity(QUERYALL_LIMIT);
+    let mut total = Duration::ZERO;
+    let extent = index.extent();
+    for c in 0..QUERYALL_LIMIT {
+        let start = time::Instant::now();
+        //
+        let queryres = index.query(&extent);
+        //
+        let end = time::Instant::now();
+        assert!(queryres.len() == index.len());
+        let diff = end - start;
+        total += diff;
+        timings.push(diff);
+        if total > QUERYALL_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
         }
-        let filename = path.file_name();
-        let filename_str = filename.to_str().unwrap();
-        let filename_wo_ext = &filename_str[..(filename_str.len() - 4)];
+    }
+    write_timings(&format!("result/queryall/{}/{filename}", I::NAME), &timings);
 
-        let count = filename_wo_ext
-            .split_once('_')
+    let etime = time::Instant::now();
+    println!("queryall done in {:?} ({total:?})", etime - stime);
+}
+
+// filename is name of env file - .size
+fn bench_querypre_index<T, I>(filename: String, index: &I)
+where
+    I: SpatialIndex<T>,
+{
+    bench_querypre_index_as(filename, index, I::NAME);
+}
+
+// like bench_querypre_index, but the timings go to result/querypre/{backend}/, for variants of
+// a backend that must not overwrite its default runs
+fn bench_querypre_index_as<T, I>(filename: String, index: &I, backend: &str)
+where
+    I: SpatialIndex<T>,
+{
+    let stime = time::Instant::now();
+
+    let bboxes = load_envelopes(&filename);
+    let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+    let mut total = Duration::ZERO;
+    for c in 0..QUERYPRE_LIMIT {
+        // for all bboxes
+        for i in 0..ENV_COUNT {
+            for (n, envs) in bboxes.iter().enumerate() {
+                let start = time::Instant::now();
+                let mut res = Vec::with_capacity(ENV_SIZES[n]);
+                index.query_with_list(&envs[i], &mut res);
+                let end = time::Instant::now();
+                assert!(res.len() == ENV_SIZES[n]);
+                let diff = end - start;
+                total += diff;
+                timings[n].push(diff);
+            }
+        }
+        if total > QUERYPRE_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    // save timings
+    let setname = envelope_set_name(&filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    for (i, size) in ENV_SIZES.iter().enumerate() {
+        write_timings(
+            &format!("result/querypre/{backend}/{setname}_{tn}.{size}"),
+            &timings[i],
+        );
+    }
+
+    let etime = time::Instant::now();
+    println!("querypre done in {:?} ({total:?})", etime - stime);
+}
+
+// one run over the querypre envelopes, one "nodes entries bbox_tests hits" line per query in
+// the order the timings are written
+fn bench_querycount_index<T, I>(filename: String, index: &I)
+where
+    T: Clone,
+    I: SpatialIndex<T> + VisitQuery<T>,
+{
+    let bboxes = load_envelopes(&filename);
+    let setname = envelope_set_name(&filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    for (n, envs) in bboxes.iter().enumerate() {
+        let mut file = File::create(format!(
+            "result/querycount/{}/{setname}_{tn}.{}",
+            I::NAME,
+            ENV_SIZES[n]
+        ))
+        .unwrap();
+        for env in envs.iter().take(ENV_COUNT) {
+            let mut counters = QueryCounters::default();
+            let mut res = Vec::with_capacity(ENV_SIZES[n]);
+            index.query_counted(env, &mut res, &mut counters);
+            assert!(res.len() == ENV_SIZES[n]);
+            file.write_all(
+                format!(
+                    "{} {} {} {}\n",
+                    counters.nodes, counters.entries, counters.bbox_tests, counters.hits
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn bench_querycount_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone,
+    I: SpatialIndex<T> + VisitQuery<T>,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| bench_querycount_index(envelopes, &I::build(data)),
+    );
+}
+
+// querypre over the ENV_FRACTIONS envelopes, so datasets of different sizes are compared at
+// the same selectivity, once for every tolerance and shape they were generated with. the hits
+// have to match the count in the file.
+fn bench_queryselectivity_index<T, I>(filename: &str, index: &I)
+where
+    I: SpatialIndex<T>,
+{
+    let stime = time::Instant::now();
+
+    let setname = envelope_set_name(filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    let mut total = Duration::ZERO;
+    for fraction in ENV_FRACTIONS {
+        let suffixes = envelope_variants(filename, fraction);
+        if suffixes.is_empty() {
+            eprintln!("no {fraction} envelopes for {filename}");
+        }
+        for suffix in suffixes {
+            let envs = load_counted_envelopes(filename, &suffix).unwrap();
+            let mut timings = Vec::with_capacity(QUERYPRE_LIMIT);
+            let mut suffix_total = Duration::ZERO;
+            for c in 0..QUERYPRE_LIMIT {
+                for (env, count) in envs.iter().take(ENV_COUNT) {
+                    let start = time::Instant::now();
+                    let mut res = Vec::with_capacity(*count);
+                    index.query_with_list(env, &mut res);
+                    let end = time::Instant::now();
+                    assert!(res.len() == *count);
+                    let diff = end - start;
+                    suffix_total += diff;
+                    timings.push(diff);
+                }
+                if suffix_total > QUERYPRE_TIME_LIMIT {
+                    eprintln!("exceeded time limit with iteration {c}!");
+                    break;
+                }
+            }
+            total += suffix_total;
+            write_timings(
+                &format!(
+                    "result/queryselectivity/{}/{setname}_{tn}.{suffix}",
+                    I::NAME
+                ),
+                &timings,
+            );
+        }
+    }
+
+    let etime = time::Instant::now();
+    println!("queryselectivity done in {:?} ({total:?})", etime - stime);
+}
+
+fn bench_queryselectivity_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    I: SpatialIndex<T>,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| bench_queryselectivity_index(&envelopes, &I::build(data)),
+    );
+}
+
+// how a query hands out its results. clones is what querypre measures, the others leave the
+// payload where it is.
+#[derive(Clone, Copy)]
+enum ResultMode {
+    Count,
+    Visit,
+    Refs,
+    Clones,
+    Ids,
+}
+
+impl ResultMode {
+    const ALL: [ResultMode; 5] = [
+        ResultMode::Count,
+        ResultMode::Visit,
+        ResultMode::Refs,
+        ResultMode::Clones,
+        ResultMode::Ids,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ResultMode::Count => "count",
+            ResultMode::Visit => "visit",
+            ResultMode::Refs => "refs",
+            ResultMode::Clones => "clones",
+            ResultMode::Ids => "ids",
+        }
+    }
+}
+
+// the number of results of env in the given mode, capacity is the expected result size
+fn query_in_mode<T, I>(index: &I, env: &BBox, mode: ResultMode, capacity: usize) -> usize
+where
+    T: Identified,
+    I: SpatialIndex<T> + VisitQuery<T>,
+{
+    match mode {
+        ResultMode::Count => index.query_count(env),
+        ResultMode::Visit => {
+            let mut count = 0;
+            index.query_visit(env, |e| {
+                black_box(e);
+                count += 1;
+            });
+            count
+        }
+        ResultMode::Refs => {
+            let mut res = Vec::with_capacity(capacity);
+            index.query_refs(env, &mut res);
+            res.len()
+        }
+        ResultMode::Clones => {
+            let mut res = Vec::with_capacity(capacity);
+            index.query_with_list(env, &mut res);
+            res.len()
+        }
+        ResultMode::Ids => {
+            let mut res = Vec::with_capacity(capacity);
+            index.query_ids(env, &mut res);
+            res.len()
+        }
+    }
+}
+
+// one result mode series: querypre and a query of the whole extent (as size "all"), written to
+// dir. query returns the number of results, the capacity is the expected result size.
+fn bench_querymodes_series<F>(bboxes: &[Vec<BBox>], extent: &BBox, len: usize, dir: &str, query: F)
+where
+    F: Fn(&BBox, usize) -> usize,
+{
+    let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+    let mut total = Duration::ZERO;
+    for c in 0..QUERYPRE_LIMIT {
+        for i in 0..ENV_COUNT {
+            for (n, envs) in bboxes.iter().enumerate() {
+                let start = time::Instant::now();
+                let count = query(&envs[i], ENV_SIZES[n]);
+                let end = time::Instant::now();
+                assert!(count == ENV_SIZES[n]);
+                let diff = end - start;
+                total += diff;
+                timings[n].push(diff);
+            }
+        }
+        if total > QUERYPRE_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    for (i, size) in ENV_SIZES.iter().enumerate() {
+        write_timings(&format!("{dir}.{size}"), &timings[i]);
+    }
+
+    let mut timings = Vec::with_capacity(QUERYALL_LIMIT);
+    let mut total = Duration::ZERO;
+    for c in 0..QUERYALL_LIMIT {
+        let start = time::Instant::now();
+        let count = query(extent, len);
+        let end = time::Instant::now();
+        assert!(count == len);
+        let diff = end - start;
+        total += diff;
+        timings.push(diff);
+        if total > QUERYALL_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    write_timings(&format!("{dir}.all"), &timings);
+}
+
+// querypre and the whole extent in every result mode
+fn bench_querymodes_index<T, I>(filename: String, index: &I)
+where
+    T: Identified,
+    I: SpatialIndex<T> + VisitQuery<T>,
+{
+    let stime = time::Instant::now();
+
+    let bboxes = load_envelopes(&filename);
+    let extent = index.extent();
+    let setname = envelope_set_name(&filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    for mode in ResultMode::ALL {
+        bench_querymodes_series(
+            &bboxes,
+            &extent,
+            index.len(),
+            &format!(
+                "result/querymodes/{}/{}/{setname}_{tn}",
+                I::NAME,
+                mode.name()
+            ),
+            |env, capacity| query_in_mode(index, env, mode, capacity),
+        );
+    }
+
+    let etime = time::Instant::now();
+    println!("querymodes done in {:?}", etime - stime);
+}
+
+// the hprtree only hands out clones, so its count and ids series are built on them and go to
+// count_via_clones and ids_via_clones. there is no visit or refs series.
+fn bench_querymodes_hprtree<T>(filename: String, index: &HPRTree<T>)
+where
+    T: Clone + Identified,
+{
+    let stime = time::Instant::now();
+
+    let bboxes = load_envelopes(&filename);
+    let extent = SpatialIndex::extent(index);
+    let len = SpatialIndex::len(index);
+    let setname = envelope_set_name(&filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    let dir = |mode: &str| format!("result/querymodes/hprtree/{mode}/{setname}_{tn}");
+    bench_querymodes_series(
+        &bboxes,
+        &extent,
+        len,
+        &dir("count_via_clones"),
+        |env, capacity| {
+            let mut res = Vec::with_capacity(capacity);
+            index.query_with_list(env, &mut res);
+            res.len()
+        },
+    );
+    bench_querymodes_series(&bboxes, &extent, len, &dir("clones"), |env, capacity| {
+        let mut res = Vec::with_capacity(capacity);
+        index.query_with_list(env, &mut res);
+        res.len()
+    });
+    bench_querymodes_series(
+        &bboxes,
+        &extent,
+        len,
+        &dir("ids_via_clones"),
+        |env, capacity| {
+            let mut res = Vec::with_capacity(capacity);
+            index.query_with_list(env, &mut res);
+            let ids: Vec<u32> = res.iter().map(|e| e.id()).collect();
+            ids.len()
+        },
+    );
+
+    let etime = time::Instant::now();
+    println!("querymodes done in {:?}", etime - stime);
+}
+
+fn bench_querymodes_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Identified,
+    I: SpatialIndex<T> + VisitQuery<T>,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| bench_querymodes_index(envelopes, &I::build(data)),
+    );
+}
+
+fn bench_querymodes_hprtree_all<T>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone + Identified,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| {
+            bench_querymodes_hprtree(envelopes, &<HPRTree<T> as SpatialIndex<T>>::build(data))
+        },
+    );
+}
+
+// repeats a join until the limits are hit and writes the timings, returns the pairs it found
+fn bench_join_method<F>(method: &str, name: &str, mut join: F) -> usize
+where
+    F: FnMut() -> usize,
+{
+    let stime = time::Instant::now();
+
+    let mut timings = Vec::with_capacity(JOIN_LIMIT);
+    let mut total = Duration::ZERO;
+    let mut pairs = 0;
+    for c in 0..JOIN_LIMIT {
+        let start = time::Instant::now();
+        pairs = join();
+        let end = time::Instant::now();
+        let diff = end - start;
+        total += diff;
+        timings.push(diff);
+        if total > JOIN_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    write_timings(&format!("result/join/{method}/{name}"), &timings);
+
+    let etime = time::Instant::now();
+    println!("{method} join done in {:?} ({total:?})", etime - stime);
+    pairs
+}
+
+// index nested loop with backend I built over b, the build is not timed
+fn bench_join_nested<T, I>(
+    a: &[(T, Point)],
+    b: &[(T, Point)],
+    name: &str,
+    pred: JoinPredicate,
+) -> usize
+where
+    T: Clone + Located,
+    I: SpatialIndex<T> + VisitQuery<T>,
+{
+    let index = I::build(b.to_vec());
+    bench_join_method(I::NAME, name, || {
+        let mut pairs = 0;
+        join::index_nested_loop(a, &index, pred, |_, _| pairs += 1);
+        pairs
+    })
+}
+
+// pairs every point of a with the points of b matching pred with every join method, name is
+// what the timing files are called. all methods have to agree on the number of pairs.
+fn bench_join<T>(a: &[(T, Point)], b: &[(T, Point)], name: &str, pred: JoinPredicate)
+where
+    T: Clone + Located + RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    let mut pairs = vec![
+        bench_join_nested::<T, RTree<T>>(a, b, name, pred),
+        bench_join_nested::<T, PackedTree<T, Bfs>>(a, b, name, pred),
+        bench_join_nested::<T, UniformGrid<T>>(a, b, name, pred),
+        bench_join_nested::<T, BallTree<T>>(a, b, name, pred),
+    ];
+
+    let tree = <HPRTree<T> as SpatialIndex<T>>::build(b.to_vec());
+    pairs.push(bench_join_method("hprtree", name, || {
+        let mut pairs = 0;
+        join::index_nested_loop_owned(a, &tree, pred, |_, _| pairs += 1);
+        pairs
+    }));
+
+    let tree = RTree::bulk_load(b.iter().map(|e| e.0.clone()).collect());
+    pairs.push(bench_join_method("rstar_sync", name, || {
+        let mut pairs = 0;
+        join::rtree_join(a, &tree, pred, |_, _| pairs += 1);
+        pairs
+    }));
+
+    pairs.push(bench_join_method("planesweep", name, || {
+        let mut pairs = 0;
+        join::plane_sweep(a, b, pred, |_, _| pairs += 1);
+        pairs
+    }));
+
+    assert!(pairs.iter().all(|&p| p == pairs[0]));
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open("result/join/pairs")
+        .unwrap();
+    file.write_all(format!("{name}: {}\n", pairs[0]).as_bytes())
+        .unwrap();
+}
+
+// population minimums from everything to large cities, each alone and within the most common
+// country of the data
+fn attribute_filters<T: Attributes>(data: &[(T, Point)]) -> Vec<AttrFilter> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (e, _) in data {
+        *counts.entry(e.country()).or_default() += 1;
+    }
+    let country = counts
+        .into_iter()
+        .max_by_key(|&(c, n)| (n, std::cmp::Reverse(c)))
+        .map(|(c, _)| c.to_string());
+    let mut filters = Vec::new();
+    for min_population in [0, 1_000, 10_000, 100_000, 1_000_000] {
+        filters.push(AttrFilter {
+            min_population,
+            country: None,
+        });
+        if let Some(country) = &country {
+            filters.push(AttrFilter {
+                min_population,
+                country: Some(country.clone()),
+            });
+        }
+    }
+    filters
+}
+
+// querypre envelopes with every filter, the results are not checked against ENV_SIZES since
+// the filters thin them out. returns the total hits per filter.
+fn bench_queryfilter_index<T, Q>(filename: &str, index: &Q, filters: &[AttrFilter]) -> Vec<usize>
+where
+    Q: FilteredQuery<T>,
+{
+    let stime = time::Instant::now();
+
+    let bboxes = load_envelopes(filename);
+    let setname = envelope_set_name(filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    let mut hits = Vec::with_capacity(filters.len());
+    for filter in filters {
+        let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+        let mut total = Duration::ZERO;
+        let mut filter_hits = 0;
+        for c in 0..QUERYPRE_LIMIT {
+            filter_hits = 0;
+            for i in 0..ENV_COUNT {
+                for (n, envs) in bboxes.iter().enumerate() {
+                    let start = time::Instant::now();
+                    let mut res = Vec::new();
+                    index.query_filtered(&envs[i], filter, &mut res);
+                    let end = time::Instant::now();
+                    filter_hits += res.len();
+                    let diff = end - start;
+                    total += diff;
+                    timings[n].push(diff);
+                }
+            }
+            if total > QUERYPRE_TIME_LIMIT {
+                eprintln!("exceeded time limit with iteration {c}!");
+                break;
+            }
+        }
+        for (i, size) in ENV_SIZES.iter().enumerate() {
+            write_timings(
+                &format!(
+                    "result/queryfilter/{}/{setname}_{tn}.{size}.{}",
+                    Q::NAME,
+                    filter.name()
+                ),
+                &timings[i],
+            );
+        }
+        hits.push(filter_hits);
+    }
+
+    let etime = time::Instant::now();
+    println!("queryfilter done in {:?}", etime - stime);
+    hits
+}
+
+// every filtering strategy on the city datasets with a population, without one every filter
+// leaves nothing. they have to agree on the hits. the share of the data passing each filter
+// goes to result/queryfilter/selectivity.
+fn bench_queryfilter_all<T>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone + Attributes,
+{
+    for_each_populated_city_dataset(opendata, simplemaps, |data, dataset, envelopes| {
+        let filters = attribute_filters(&data);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open("result/queryfilter/selectivity")
+            .unwrap();
+        for filter in &filters {
+            let passing = data.iter().filter(|e| filter.matches(&e.0)).count();
+            let fraction = passing as f64 / data.len() as f64;
+            file.write_all(format!("{dataset}.{}: {fraction}\n", filter.name()).as_bytes())
+                .unwrap();
+        }
+
+        let hits = [
+            bench_queryfilter_index(&envelopes, &PostFilter::build(data.clone()), &filters),
+            bench_queryfilter_index(&envelopes, &Partitioned::build(data.clone()), &filters),
+            bench_queryfilter_index(&envelopes, &SummaryTree::build(data), &filters),
+        ];
+        assert!(hits.iter().all(|h| *h == hits[0]));
+    });
+}
+
+// count/sum queries over the querypre envelopes, their counts have to match ENV_SIZES. returns
+// the aggregates of the first run in the order the timings are written.
+fn bench_queryaggregate_index<F>(method: &str, filename: &str, tn: &str, query: F) -> Vec<Aggregate>
+where
+    F: Fn(&BBox) -> Aggregate,
+{
+    let stime = time::Instant::now();
+
+    let bboxes = load_envelopes(filename);
+    let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+    let mut aggregates = Vec::with_capacity(ENV_SIZES.len() * ENV_COUNT);
+    let mut total = Duration::ZERO;
+    for c in 0..QUERYPRE_LIMIT {
+        for (n, envs) in bboxes.iter().enumerate() {
+            for env in envs.iter().take(ENV_COUNT) {
+                let start = time::Instant::now();
+                let res = query(env);
+                let end = time::Instant::now();
+                assert!(res.count == ENV_SIZES[n]);
+                if c == 0 {
+                    aggregates.push(res);
+                }
+                let diff = end - start;
+                total += diff;
+                timings[n].push(diff);
+            }
+        }
+        if total > QUERYPRE_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    let setname = envelope_set_name(filename);
+    for (i, size) in ENV_SIZES.iter().enumerate() {
+        write_timings(
+            &format!("result/queryaggregate/{method}/{setname}_{tn}.{size}"),
+            &timings[i],
+        );
+    }
+
+    let etime = time::Instant::now();
+    println!("queryaggregate done in {:?} ({total:?})", etime - stime);
+    aggregates
+}
+
+// the aggregate tree against counting and summing the hits of rstar and the packed tree, on
+// every dataset. all of them have to agree.
+fn bench_queryaggregate_all<T>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone + Attributes + RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    let tn = &std::any::type_name::<T>()[6..];
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, dataset, envelopes| {
+            let tree = AggregateTree::build(data.clone());
+            assert!(tree.len() == data.len());
+            append_szfile(
+                AggregateTree::NAME,
+                &format!("{dataset}_{tn}: {}", tree.size_in_bytes()),
+            );
+            let aggregates =
+                bench_queryaggregate_index(AggregateTree::NAME, &envelopes, tn, |env| {
+                    tree.query_aggregate(env)
+                });
+            drop(tree);
+
+            let rtree = RTree::bulk_load(data.iter().map(|e| e.0.clone()).collect());
+            let rstar = bench_queryaggregate_index("rstar", &envelopes, tn, |env| {
+                aggregate::visit_aggregate(&rtree, env)
+            });
+            assert!(rstar == aggregates);
+            drop(rtree);
+
+            let packed = PackedTree::<T, Bfs>::build(data);
+            let packed_bfs = bench_queryaggregate_index(
+                <PackedTree<T, Bfs> as SpatialIndex<T>>::NAME,
+                &envelopes,
+                tn,
+                |env| aggregate::visit_aggregate(&packed, env),
+            );
+            assert!(packed_bfs == aggregates);
+        },
+    );
+}
+
+// top-k queries over the querypre envelopes for every k, returns the ranks of the results of
+// the first run so the methods can be compared
+fn bench_querytopk_index<T, K, F>(method: &str, filename: &str, query: F) -> Vec<Option<i64>>
+where
+    K: RankKey<T>,
+    F: Fn(&BBox, usize, &mut Vec<T>),
+{
+    let stime = time::Instant::now();
+
+    let bboxes = load_envelopes(filename);
+    let setname = envelope_set_name(filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    let mut ranks = Vec::new();
+    for k in TOPK_SIZES {
+        let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+        let mut total = Duration::ZERO;
+        for c in 0..QUERYPRE_LIMIT {
+            for (n, envs) in bboxes.iter().enumerate() {
+                for env in envs.iter().take(ENV_COUNT) {
+                    let start = time::Instant::now();
+                    let mut res = Vec::with_capacity(k);
+                    query(env, k, &mut res);
+                    let end = time::Instant::now();
+                    assert!(res.len() <= k.min(ENV_SIZES[n]));
+                    if c == 0 {
+                        ranks.extend(res.iter().map(K::rank));
+                    }
+                    let diff = end - start;
+                    total += diff;
+                    timings[n].push(diff);
+                }
+            }
+            if total > QUERYPRE_TIME_LIMIT {
+                eprintln!("exceeded time limit with iteration {c}!");
+                break;
+            }
+        }
+        for (i, size) in ENV_SIZES.iter().enumerate() {
+            write_timings(
+                &format!(
+                    "result/querytopk/{method}/{setname}_{tn}.{size}.{}_k{k}",
+                    K::NAME
+                ),
+                &timings[i],
+            );
+        }
+    }
+
+    let etime = time::Instant::now();
+    println!("querytopk done in {:?}", etime - stime);
+    ranks
+}
+
+// best-first top-k against filter and sort on a packed tree, both have to rank the same
+fn bench_querytopk<T, K>(data: Vec<(T, Point)>, envelopes: &str)
+where
+    T: Clone,
+    K: RankKey<T>,
+{
+    let tree = TopKTree::<T, K>::build(data.clone());
+    let best = bench_querytopk_index::<T, K, _>("topktree", envelopes, |env, k, res| {
+        tree.query_top_k(env, k, res)
+    });
+    drop(tree);
+
+    let packed = PackedTree::<T, Bfs>::build(data);
+    let naive = bench_querytopk_index::<T, K, _>("naive", envelopes, |env, k, res| {
+        topk::naive_top_k::<T, K, _>(&packed, env, k, res)
+    });
+    assert!(best == naive);
+}
+
+// the workloads the replay runs when none are given with --workload, hotspots on a few of the
+// largest cities
+fn default_workloads() -> Vec<Centres> {
+    let hotspot = |lon, lat, weight| Hotspot {
+        lon,
+        lat,
+        radius: 0.5f32,
+        weight,
+    };
+    vec![
+        Centres::Zipf(1f64),
+        Centres::Population,
+        Centres::Hotspots(vec![
+            hotspot(139.69f32, 35.69f32, 4f64),
+            hotspot(77.21f32, 28.61f32, 3f64),
+            hotspot(121.47f32, 31.23f32, 3f64),
+            hotspot(-46.63f32, -23.55f32, 2f64),
+            hotspot(-74.01f32, 40.71f32, 2f64),
+            hotspot(3.38f32, 6.52f32, 1f64),
+        ]),
+    ]
+}
+
+// runs every stream against index and prints its latency percentiles, the timings go to
+// result/replay/{I::NAME}/{setname}_{tn}.{stream}
+fn bench_replay_index<T, I>(index: &I, streams: &[(String, Vec<BBox>)], setname: &str)
+where
+    I: SpatialIndex<T>,
+{
+    let tn = &std::any::type_name::<T>()[6..];
+    for (stream, queries) in streams {
+        let mut timings = Vec::with_capacity(queries.len());
+        let mut total = Duration::ZERO;
+        let mut hits = 0;
+        for env in queries {
+            let start = time::Instant::now();
+            let mut res = Vec::new();
+            index.query_with_list(env, &mut res);
+            let end = time::Instant::now();
+            hits += res.len();
+            let diff = end - start;
+            total += diff;
+            timings.push(diff);
+            if total > QUERYPRE_TIME_LIMIT {
+                eprintln!("exceeded time limit after {} queries!", timings.len());
+                break;
+            }
+        }
+        write_timings(
+            &format!("result/replay/{}/{setname}_{tn}.{stream}", I::NAME),
+            &timings,
+        );
+        println!(
+            "{} {setname} {stream}: {} ({} queries, {hits} hits)",
+            I::NAME,
+            percentiles(&timings),
+            timings.len()
+        );
+    }
+}
+
+// the REPLAY_PERCENTILES of the timings for printing
+fn percentiles(timings: &[Duration]) -> String {
+    let mut sorted = timings.to_vec();
+    sorted.sort();
+    let percentiles: Vec<String> = REPLAY_PERCENTILES
+        .iter()
+        .map(|&p| format!("p{}: {:?}", p * 100f64, workload::percentile(&sorted, p)))
+        .collect();
+    percentiles.join(", ")
+}
+
+// long streams of querypre sized envelopes centred by each workload, replayed against rstar,
+// the hprtree and the packed tree
+fn bench_replay<T>(data: Vec<(T, Point)>, envelopes: &str, workloads: &[Centres])
+where
+    T: Clone + Attributes + RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    let stime = time::Instant::now();
+
+    let setname = envelope_set_name(envelopes);
+    let bboxes = load_envelopes(envelopes);
+    let mut streams = Vec::new();
+    for centres in workloads {
+        for (n, size) in ENV_SIZES.iter().enumerate() {
+            let seed = REPLAY_SEED + n as u64;
+            match workload::generate(centres, &data, &bboxes[n], REPLAY_LEN, seed) {
+                Some(queries) => streams.push((format!("{}.{size}", centres.name()), queries)),
+                None => eprintln!("no {} workload for {setname}", centres.name()),
+            }
+        }
+    }
+
+    bench_replay_index(&RTree::build(data.clone()), &streams, &setname);
+    bench_replay_index(&HPRTree::build(data.clone()), &streams, &setname);
+    bench_replay_index(&PackedTree::<T, Bfs>::build(data), &streams, &setname);
+
+    let etime = time::Instant::now();
+    println!("replay done in {:?}", etime - stime);
+}
+
+// the logs replayed on a dataset: its envelope files of every tolerance and shape imported as
+// logs, then the files in ../../data/querylogs/{dataset}/
+fn query_logs(dataset: &str, envelopes: &str) -> Vec<(String, Vec<LoggedQuery>)> {
+    let mut logs = Vec::new();
+    let targets = ENV_SIZES
+        .iter()
+        .map(|size| (size.to_string(), Some(*size)))
+        .chain(
+            ENV_FRACTIONS
+                .iter()
+                .map(|fraction| (fraction.to_string(), None)),
+        );
+    for (target, size) in targets {
+        for suffix in envelope_variants(envelopes, &target) {
+            let path = format!("{envelopes}.{suffix}");
+            if let Some(log) = querylog::import_envelopes(Path::new(&path), size) {
+                logs.push((format!("envelopes{suffix}"), log));
+            }
+        }
+    }
+    let mut paths: Vec<_> = match fs::read_dir(format!("../../data/querylogs/{dataset}/")) {
+        Ok(entries) => entries.map(|e| e.unwrap().path()).collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    for path in paths {
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        logs.push((name, querylog::read_log(path.to_str().unwrap())));
+    }
+    logs
+}
+
+// replays every log against index and prints the latency percentiles, how many results differ
+// from the expected ones and how many queries the backend doesn't support. the timings of the
+// supported ones go to
+// result/replaylog/{I::NAME}/{setname}_{tn}.{log}.{pace}, next to them the log again with the
+// result counts of this run as the expected ones.
+fn bench_replaylog_index<T, I>(
+    index: &I,
+    logs: &[(String, Vec<LoggedQuery>)],
+    setname: &str,
+    pace: Pace,
+) where
+    I: Replay<T>,
+{
+    let tn = &std::any::type_name::<T>()[6..];
+    for (name, log) in logs {
+        let results = querylog::replay(index, log, pace, QUERYPRE_TIME_LIMIT);
+        let timings: Vec<Duration> = results.iter().flatten().map(|r| r.0).collect();
+        let unsupported = results.iter().filter(|r| r.is_none()).count();
+        let mismatches = log
+            .iter()
+            .zip(&results)
+            .filter(|(q, r)| matches!((q.expected, r), (Some(e), Some(r)) if e != r.1))
+            .count();
+        let path = format!(
+            "result/replaylog/{}/{setname}_{tn}.{name}.{}",
+            I::NAME,
+            pace.name()
+        );
+        write_timings(&path, &timings);
+        querylog::write_log(
+            &format!("{path}.log"),
+            log.iter()
+                .zip(&results)
+                .map(|(q, r)| (q, r.map_or(q.expected, |r| Some(r.1)))),
+        );
+        println!(
+            "{} {setname} {name}: {} ({} queries, {mismatches} unexpected results, \
+             {unsupported} unsupported)",
+            I::NAME,
+            percentiles(&timings),
+            results.len()
+        );
+    }
+}
+
+fn bench_replaylog_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+    pace: Pace,
+) where
+    I: Replay<T>,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, dataset, envelopes| {
+            let logs = query_logs(dataset, &envelopes);
+            let setname = envelope_set_name(&envelopes);
+            bench_replaylog_index(&I::build(data), &logs, &setname, pace);
+        },
+    );
+}
+
+fn synthetic_180x90x_x<T>(mult: u32, gen: fn(f32, f32, u32) -> (T, Point)) -> Vec<(T, Point)> {
+    let submult = (mult as f32).sqrt();
+    let d = 2f32 / submult;
+    let mut data = Vec::with_capacity(180 * 90 * mult as usize);
+    let mut x = -180f32;
+    for i in 0..(180 * submult as u32) {
+        let mut y = -90f32;
+        for j in 0..(90 * submult as u32) {
+            data.push(gen(x, y, i * 10000u32 + j));
+            y += d;
+        }
+        x += d;
+    }
+    assert!(data.len() == 180 * 90 * mult as usize);
+    data
+}
+
+// files in dir together with their element count, which is encoded in the file name
+fn random_datasets(dir: &str, clustered: bool) -> Vec<(String, String, usize)> {
+    let mut datasets = Vec::new();
+    for path in fs::read_dir(dir).unwrap() {
+        let path = path.unwrap();
+        if !path.metadata().unwrap().is_file() {
+            continue;
+        }
+        let filename = path.file_name();
+        let filename_str = filename.to_str().unwrap();
+        let filename_wo_ext = &filename_str[..(filename_str.len() - 4)];
+
+        let mut split = filename_wo_ext.split('_');
+        let mut count = split.next().unwrap().parse::<usize>().unwrap();
+        if clustered {
+            count *= split.next().unwrap().parse::<usize>().unwrap();
+        }
+        datasets.push((
+            path.path().to_str().unwrap().to_string(),
+            filename_wo_ext.to_string(),
+            count,
+        ));
+    }
+    datasets
+}
+
+fn bench_index_dataset<T, I>(data: Vec<(T, Point)>, dataset: &str, envelopes: String)
+where
+    T: Clone,
+    I: SpatialIndex<T>,
+{
+    let backend = I::NAME;
+    let tn = &std::any::type_name::<T>()[6..];
+    let index: I = bench_build_index(data, &format!("bench_build_{backend}_{dataset}_{tn}"));
+    bench_queryall_index(format!("bench_queryall_{backend}_{dataset}_{tn}"), &index);
+    bench_querypre_index(envelopes, &index);
+}
+
+// grid with exactly one cell per lattice point of the synthetic data instead of the automatic resolution
+fn bench_grid_synthetic_180x90x_x_fixed<T>(mult: u32, gen: fn(f32, f32, u32) -> (T, Point))
+where
+    T: Clone,
+{
+    let tn = &std::any::type_name::<T>()[6..];
+    let submult = (mult as f32).sqrt() as usize;
+    let resolution = || GridResolution::Fixed(180 * submult, 90 * submult);
+
+    let grid = bench_build_index_with(
+        synthetic_180x90x_x(mult, gen),
+        &format!("bench_build_grid_synthetic_180x90x{mult}_fixed_{tn}"),
+        |data| UniformGrid::with_resolution(data, resolution()),
+    );
+    let (nx, ny) = grid.resolution();
+    println!("grid resolution {nx}x{ny}");
+    bench_queryall_index(
+        format!("bench_queryall_grid_synthetic_180x90x{mult}_fixed_{tn}"),
+        &grid,
+    );
+    bench_querypre_index_as(
+        format!("../../data/envelopes/ordered/{mult}"),
+        &grid,
+        "grid_fixed",
+    );
+}
+
+fn with_opendata<T, F>(deser: fn(StringRecord) -> Option<(T, Point)>, f: &mut F)
+where
+    F: FnMut(Vec<(T, Point)>, &str, String),
+{
+    f(
+        read(
+            b';',
+            "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
+            140974,
+            deser,
+        ),
+        "opendata",
+        "../../data/envelopes/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv"
+            .to_string(),
+    );
+}
+
+fn with_simplemaps<T, F>(deser: fn(StringRecord) -> Option<(T, Point)>, f: &mut F)
+where
+    F: FnMut(Vec<(T, Point)>, &str, String),
+{
+    f(
+        read(
+            b',',
+            "../../data/base/simplemaps/worldcities.csv",
+            44692,
+            deser,
+        ),
+        "simplemaps",
+        "../../data/envelopes/base/simplemaps/worldcities.csv".to_string(),
+    );
+}
+
+// calls f(data, dataset name, envelope file) for the three real city datasets
+fn for_each_city_dataset<T, F>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    mut f: F,
+) where
+    F: FnMut(Vec<(T, Point)>, &str, String),
+{
+    with_opendata(opendata, &mut f);
+    f(
+        read(
+            b',',
+            "../../data/base/matthewproctor/worldcities-geo.csv",
+            3808651,
+            matthe,
+        ),
+        "matthe",
+        "../../data/envelopes/base/matthewproctor/worldcities-geo.csv".to_string(),
+    );
+    with_simplemaps(simplemaps, &mut f);
+}
+
+// like for_each_city_dataset, but only the ones with a population column, matthe has none
+fn for_each_populated_city_dataset<T, F>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    mut f: F,
+) where
+    F: FnMut(Vec<(T, Point)>, &str, String),
+{
+    with_opendata(opendata, &mut f);
+    with_simplemaps(simplemaps, &mut f);
+}
+
+// calls f(data, dataset name, envelope file) for every dataset the hprtree and rstar benches use
+fn for_each_dataset<T, F>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+    mut f: F,
+) where
+    F: FnMut(Vec<(T, Point)>, &str, String),
+{
+    for_each_city_dataset(opendata, matthe, simplemaps, &mut f);
+    for mult in [1, 4, 16, 64, 256] {
+        f(
+            synthetic_180x90x_x(mult, synthetic),
+            &format!("synthetic_180x90x{mult}"),
+            format!("../../data/envelopes/ordered/{mult}"),
+        );
+    }
+    for (kind, clustered) in [("uniform", false), ("clustered", true)] {
+        for (path, name, count) in random_datasets(&format!("../../data/new/{kind}/"), clustered) {
+            f(
+                read(b',', &path, count, random),
+                &format!("{kind}_{name}"),
+                path.replace("/data", "/data/envelopes"),
+            );
+        }
+    }
+}
+
+// runs build, queryall and querypre for backend I on every dataset
+fn bench_index_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone,
+    I: SpatialIndex<T>,
+{
+    println!("starting build...");
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, dataset, envelopes| bench_index_dataset::<T, I>(data, dataset, envelopes),
+    );
+}
+
+// radius queries around the centers of the querypre envelopes, reaching out to their corners
+fn bench_queryradius_index<T, I>(filename: String, index: &I)
+where
+    I: SpatialIndex<T> + RadiusQuery<T>,
+{
+    let stime = time::Instant::now();
+
+    let circles: Vec<Vec<(Point, f64)>> = load_envelopes(&filename)
+        .iter()
+        .map(|envs| {
+            envs.iter()
+                .map(|e| {
+                    let center = Point {
+                        x: (e.minx + e.maxx) / 2f32,
+                        y: (e.miny + e.maxy) / 2f32,
+                    };
+                    let corner = Point {
+                        x: e.minx,
+                        y: e.miny,
+                    };
+                    let radius = sphere::haversine_m(&center, &corner);
+                    (center, radius)
+                })
+                .collect()
+        })
+        .collect();
+    let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+    let mut counts = vec![0; ENV_SIZES.len()];
+    let mut total = Duration::ZERO;
+    for c in 0..QUERYPRE_LIMIT {
+        for i in 0..ENV_COUNT {
+            for (n, circles) in circles.iter().enumerate() {
+                let start = time::Instant::now();
+                let mut res = Vec::with_capacity(ENV_SIZES[n]);
+                index.query_radius(&circles[i].0, circles[i].1, &mut res);
+                let end = time::Instant::now();
+                if c == 0 {
+                    counts[n] += res.len();
+                }
+                let diff = end - start;
+                total += diff;
+                timings[n].push(diff);
+            }
+        }
+        if total > QUERYPRE_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    let setname = envelope_set_name(&filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    for (i, size) in ENV_SIZES.iter().enumerate() {
+        write_timings(
+            &format!("result/queryradius/{}/{setname}_{tn}.{size}", I::NAME),
+            &timings[i],
+        );
+        // total hits of the ENV_COUNT circles, the same for every backend
+        append_szfile(
+            &format!("{}_radiushits", I::NAME),
+            &format!("{setname}_{tn}.{size}: {}", counts[i]),
+        );
+    }
+
+    let etime = time::Instant::now();
+    println!("queryradius done in {:?} ({total:?})", etime - stime);
+}
+
+// centers of the querypre envelopes, k is the envelope's target size
+fn bench_queryknn_index<T, I>(filename: String, index: &I)
+where
+    I: SpatialIndex<T> + KnnQuery<T>,
+{
+    let stime = time::Instant::now();
+
+    let centers: Vec<Vec<Point>> = load_envelopes(&filename)
+        .iter()
+        .map(|envs| {
+            envs.iter()
+                .map(|e| Point {
+                    x: (e.minx + e.maxx) / 2f32,
+                    y: (e.miny + e.maxy) / 2f32,
+                })
+                .collect()
+        })
+        .collect();
+    let mut timings = vec![Vec::with_capacity(QUERYPRE_LIMIT); ENV_SIZES.len()];
+    let mut total = Duration::ZERO;
+    for c in 0..QUERYPRE_LIMIT {
+        for i in 0..ENV_COUNT {
+            for (n, centers) in centers.iter().enumerate() {
+                let start = time::Instant::now();
+                let mut res = Vec::with_capacity(ENV_SIZES[n]);
+                index.query_knn(&centers[i], ENV_SIZES[n], &mut res);
+                let end = time::Instant::now();
+                assert!(res.len() == ENV_SIZES[n].min(index.len()));
+                let diff = end - start;
+                total += diff;
+                timings[n].push(diff);
+            }
+        }
+        if total > QUERYPRE_TIME_LIMIT {
+            eprintln!("exceeded time limit with iteration {c}!");
+            break;
+        }
+    }
+    let setname = envelope_set_name(&filename);
+    let tn = &std::any::type_name::<T>()[6..];
+    for (i, size) in ENV_SIZES.iter().enumerate() {
+        write_timings(
+            &format!("result/queryknn/{}/{setname}_{tn}.{size}", I::NAME),
+            &timings[i],
+        );
+    }
+
+    let etime = time::Instant::now();
+    println!("queryknn done in {:?} ({total:?})", etime - stime);
+}
+
+fn bench_queryknn_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone,
+    I: SpatialIndex<T> + KnnQuery<T>,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| bench_queryknn_index(envelopes, &I::build(data)),
+    );
+}
+
+// hex bins of a dataset as csv: cell, count, center and the boundary as a wkt polygon
+fn write_hex_bins<T>(index: &HexIndex<T>, dataset: &str) {
+    let mut file = File::create(format!("result/hexbins/{dataset}.csv")).unwrap();
+    file.write_all(b"cell,count,lon,lat,boundary\n").unwrap();
+    for (cell, count) in index.bins() {
+        let center = cell.center();
+        let mut boundary = cell.boundary();
+        boundary.push(boundary[0].clone());
+        let ring: Vec<String> = boundary
+            .iter()
+            .map(|p| format!("{} {}", p.x, p.y))
+            .collect();
+        file.write_all(
+            format!(
+                "{:x},{count},{},{},\"POLYGON(({}))\"\n",
+                cell.0,
+                center.x,
+                center.y,
+                ring.join(", ")
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    }
+}
+
+// cost of the cell operations themselves: covering and compacting the querypre envelopes at
+// the index resolution, and k-rings around their centers. compacting is approximate, so the
+// cover lines also hold how many cells uncompacting the result loses and adds.
+fn bench_hexops(filename: String, res: u8) {
+    let stime = time::Instant::now();
+
+    let setname = envelope_set_name(&filename);
+    let bboxes = load_envelopes(&filename);
+    for (n, size) in ENV_SIZES.iter().enumerate() {
+        let mut file = File::create(format!("result/hexops/{setname}.{size}")).unwrap();
+        for env in &bboxes[n] {
+            let start = time::Instant::now();
+            let cover = hex::cover_bbox(env, res);
+            let mid = time::Instant::now();
+            let compacted = hex::compact(&cover);
+            let end = time::Instant::now();
+            // compact is approximate, the cover cells it lost and the ones it added
+            let restored = hex::uncompact(&compacted, res);
+            let lost = cover.iter().filter(|c| restored.binary_search(c).is_err());
+            let added = restored.iter().filter(|c| cover.binary_search(c).is_err());
+            file.write_all(
+                format!(
+                    "{} {} {} {} {} {}\n",
+                    cover.len(),
+                    compacted.len(),
+                    (mid - start).as_nanos(),
+                    (end - mid).as_nanos(),
+                    lost.count(),
+                    added.count()
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        }
+    }
+    let mut file = File::create(format!("result/hexops/{setname}.kring")).unwrap();
+    for env in &bboxes[0] {
+        let center = Point {
+            x: (env.minx + env.maxx) / 2f32,
+            y: (env.miny + env.maxy) / 2f32,
+        };
+        let cell = hex::HexCell::from_point(&center, res);
+        for k in [1, 2, 4, 8] {
+            let start = time::Instant::now();
+            let ring = cell.k_ring(k);
+            let end = time::Instant::now();
+            file.write_all(format!("{k} {} {}\n", ring.len(), (end - start).as_nanos()).as_bytes())
+                .unwrap();
+        }
+    }
+
+    let etime = time::Instant::now();
+    println!("hexops done in {:?}", etime - stime);
+}
+
+fn bench_queryradius_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone,
+    I: SpatialIndex<T> + RadiusQuery<T>,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| bench_queryradius_index(envelopes, &I::build(data)),
+    );
+}
+
+// the slab tree with every coordinate encoding, run for each element type to see how the
+// payload size shows up when it is kept out of the leaves
+fn bench_storage_modes<T>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone,
+{
+    bench_index_all::<_, SlabTree<_, F32>>(opendata, matthe, simplemaps, synthetic, random);
+    bench_index_all::<_, SlabTree<_, Q32>>(opendata, matthe, simplemaps, synthetic, random);
+    bench_index_all::<_, SlabTree<_, Q16>>(opendata, matthe, simplemaps, synthetic, random);
+}
+
+// querypre on one packed tree per dataset, stored in each of the node layouts in turn
+fn bench_querypre_layouts<T>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+    synthetic: fn(f32, f32, u32) -> (T, Point),
+    random: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    T: Clone,
+{
+    for_each_dataset(
+        opendata,
+        matthe,
+        simplemaps,
+        synthetic,
+        random,
+        |data, _, envelopes| {
+            let bfs = PackedTree::<T, Bfs>::build(data);
+            bench_querypre_index(envelopes.clone(), &bfs);
+            let dfs = bfs.with_layout::<Dfs>();
+            bench_querypre_index(envelopes.clone(), &dfs);
+            let veb = dfs.with_layout::<Veb>();
+            bench_querypre_index(envelopes, &veb);
+        },
+    );
+}
+
+// radius and knn queries on the real city datasets, where great circle distances matter
+fn bench_metric_all<T, I>(
+    opendata: fn(StringRecord) -> Option<(T, Point)>,
+    matthe: fn(StringRecord) -> Option<(T, Point)>,
+    simplemaps: fn(StringRecord) -> Option<(T, Point)>,
+) where
+    I: SpatialIndex<T> + RadiusQuery<T> + KnnQuery<T>,
+{
+    for_each_city_dataset(opendata, matthe, simplemaps, |data, _, envelopes| {
+        let index = I::build(data);
+        bench_queryradius_index(envelopes.clone(), &index);
+        bench_queryknn_index(envelopes, &index);
+    });
+}
+
+trait TestSize {
+    fn size_in_bytes(&self) -> usize;
+}
+
+fn size_in_bytes_helper<T>(node: &ParentNode<T>) -> usize
+where
+    T: RTreeObject,
+{
+    let mut sum = 0;
+    for child in node.children() {
+        sum += std::mem::size_of_val(child);
+        match child {
+            rstar::RTreeNode::Leaf(_) => (),
+            rstar::RTreeNode::Parent(subparent) => sum += size_in_bytes_helper(subparent),
+        }
+    }
+    sum
+}
+
+impl<T> TestSize for RTree<T>
+where
+    T: RTreeObject,
+{
+    fn size_in_bytes(&self) -> usize {
+        let internal_sz = size_in_bytes_helper(self.root());
+        std::mem::size_of_val(self) + internal_sz
+    }
+}
+
+fn bench_hprtree_opendata<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+where
+    T: Clone,
+{
+    println!("starting build...");
+    let count = 140974;
+    let tn = &std::any::type_name::<T>()[6..];
+
+    let data = read(
+        b';',
+        "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
+        count,
+        deser,
+    );
+
+    let tree = bench_build_hprtree(data, &format!("bench_build_hprtree_opendata_{tn}"));
+    bench_queryall_hprtree(format!("bench_queryall_hprtree_opendata_{tn}"), &tree);
+    bench_querypre_hprtree(
+        "../../data/envelopes/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv"
+            .to_string(),
+        &tree,
+    );
+}
+
+fn bench_rstar_opendata<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+where
+    T: Clone,
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    println!("starting build...");
+    let tn = &std::any::type_name::<T>()[6..];
+    let count = 140974;
+
+    let data = read(
+        b';',
+        "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
+        count,
+        deser,
+    )
+    .into_iter()
+    .map(|e| e.0)
+    .collect();
+
+    let tree = bench_build_rstar(data, &format!("bench_build_rstar_opendata_{tn}"));
+    bench_queryall_rtree(format!("bench_queryall_rstar_opendata_{tn}"), &tree);
+    bench_querypre_rstar(
+        "../../data/envelopes/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv"
+            .to_string(),
+        &tree,
+    );
+}
+
+fn bench_hprtree_random_uniform<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+where
+    T: Clone,
+{
+    let tn = &std::any::type_name::<T>()[6..];
+    let paths = fs::read_dir("../../data/new/uniform/").unwrap();
+    for path in paths {
+        let path = path.unwrap();
+        if !path.metadata().unwrap().is_file() {
+            continue;
+        }
+        let filename = path.file_name();
+        let filename_str = filename.to_str().unwrap();
+        let filename_wo_ext = &filename_str[..(filename_str.len() - 4)];
+
+        let count = filename_wo_ext
+            .split_once('_')
+            .unwrap()
+            .0
+            .parse::<usize>()
+            .unwrap();
+        let p = path.path();
+        let pathstr = p.to_str().unwrap();
+        let data = read(b',', pathstr, count, deser);
+
+        let tree = bench_build_hprtree(
+            data,
+            &format!("bench_build_hprtree_uniform_{filename_wo_ext}_{tn}",),
+        );
+        bench_queryall_hprtree(
+            format!("bench_queryall_hprtree_uniform_{filename_wo_ext}_{tn}"),
+            &tree,
+        );
+        bench_querypre_hprtree(pathstr.replace("/data", "/data/envelopes"), &tree);
+    }
+}
+
+fn bench_rstar_random_uniform<T>(deser: fn(StringRecord) -> Option<(T, Point)>)
+where
+    T: Clone,
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    let tn = &std::any::type_name::<T>()[6..];
+    let paths = fs::read_dir("../../data/new/uniform/").unwrap();
+    for path in paths {
+        let path = path.unwrap();
+        if !path.metadata().unwrap().is_file() {
+            continue;
+        }
+        let filename = path.file_name();
+        let filename_str = filename.to_str().unwrap();
+        let filename_wo_ext = &filename_str[..(filename_str.len() - 4)];
+
+        let count = filename_wo_ext
+            .split_once('_')
             .unwrap()
             .0
             .parse::<usize>()
@@ -914,15 +2850,186 @@ where
     .map(|e| e.0)
     .collect();
 
-    let tree = bench_build_rstar(data, &format!("bench_build_rstar_matthe_{tn}"));
-    bench_queryall_rtree(format!("bench_queryall_rstar_matthe_{tn}"), &tree);
-    bench_querypre_rstar(
-        "../../data/envelopes/base/matthewproctor/worldcities-geo.csv".to_string(),
-        &tree,
+    let tree = bench_build_rstar(data, &format!("bench_build_rstar_matthe_{tn}"));
+    bench_queryall_rtree(format!("bench_queryall_rstar_matthe_{tn}"), &tree);
+    bench_querypre_rstar(
+        "../../data/envelopes/base/matthewproctor/worldcities-geo.csv".to_string(),
+        &tree,
+    );
+}
+
+// matches the three city sources pairwise and writes the pairs, the records without any match
+// and the merged table to result/matching/
+fn run_matching() {
+    let stime = time::Instant::now();
+    create_dir_all(Path::new("result/matching/")).unwrap();
+
+    let mut sources: Vec<(&str, Vec<(City, Point)>)> = vec![
+        (
+            "opendata",
+            read(
+                b';',
+                "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
+                140974,
+                matching::opendata_city,
+            ),
+        ),
+        (
+            "simplemaps",
+            read(
+                b',',
+                "../../data/base/simplemaps/worldcities.csv",
+                44692,
+                matching::simplemaps_city,
+            ),
+        ),
+        (
+            "matthe",
+            read(
+                b',',
+                "../../data/base/matthewproctor/worldcities-geo.csv",
+                3808651,
+                matching::matthe_city,
+            ),
+        ),
+    ];
+    for (_, cities) in &mut sources {
+        matching::number_cities(cities);
+    }
+
+    let mut matched: Vec<Vec<bool>> = sources.iter().map(|s| vec![false; s.1.len()]).collect();
+    let mut all_matches = Vec::new();
+    for (sa, sb) in [(0, 1), (0, 2), (1, 2)] {
+        let (na, a) = (sources[sa].0, &sources[sa].1);
+        let (nb, b) = (sources[sb].0, &sources[sb].1);
+        let matches = matching::match_sources(a, b);
+        write_matches(&format!("result/matching/{na}-{nb}.csv"), a, b, &matches);
+        for m in &matches {
+            matched[sa][m.a as usize] = true;
+            matched[sb][m.b as usize] = true;
+        }
+        println!(
+            "{na}-{nb}: {} matches, {} of {na} and {} of {nb} unmatched",
+            matches.len(),
+            a.len() - matches.len(),
+            b.len() - matches.len()
+        );
+        all_matches.push((sa, sb, matches));
+    }
+
+    for (s, (name, cities)) in sources.iter().enumerate() {
+        let mut writer =
+            csv::Writer::from_path(format!("result/matching/unmatched_{name}.csv")).unwrap();
+        writer.write_record(["id", "name", "lat", "lon"]).unwrap();
+        let mut unmatched = 0;
+        for (c, p) in cities.iter().filter(|c| !matched[s][c.0.idx as usize]) {
+            writer
+                .write_record([&c.id, &c.name, &p.y.to_string(), &p.x.to_string()])
+                .unwrap();
+            unmatched += 1;
+        }
+        println!("{name}: {unmatched} of {} without any match", cities.len());
+    }
+
+    // one row per group, the coordinates and name of the first source that has the city
+    let sizes: Vec<usize> = sources.iter().map(|s| s.1.len()).collect();
+    let groups = matching::merge(&sizes, &all_matches);
+    let mut writer = csv::Writer::from_path("result/matching/merged.csv").unwrap();
+    let mut header = vec!["name", "lat", "lon", "consistent"];
+    header.extend(sources.iter().map(|s| s.0));
+    writer.write_record(&header).unwrap();
+    let mut conflicts = 0;
+    for group in &groups {
+        let mut ids = vec![Vec::new(); sources.len()];
+        for &(s, i) in group {
+            ids[s].push(sources[s].1[i as usize].0.id.as_str());
+        }
+        let consistent = ids.iter().all(|ids| ids.len() <= 1);
+        if !consistent {
+            conflicts += 1;
+        }
+        let (s, i) = group.iter().min().unwrap();
+        let (c, p) = &sources[*s].1[*i as usize];
+        let mut row = vec![
+            c.name.clone(),
+            p.y.to_string(),
+            p.x.to_string(),
+            consistent.to_string(),
+        ];
+        row.extend(ids.iter().map(|ids| ids.join("|")));
+        writer.write_record(&row).unwrap();
+    }
+    println!("{} merged cities, {conflicts} inconsistent", groups.len());
+
+    let etime = time::Instant::now();
+    println!("matching done in {:?}", etime - stime);
+}
+
+fn write_matches(path: &str, a: &[(City, Point)], b: &[(City, Point)], matches: &[Match]) {
+    let mut writer = csv::Writer::from_path(path).unwrap();
+    writer
+        .write_record([
+            "id_a",
+            "id_b",
+            "name_a",
+            "name_b",
+            "distance_m",
+            "name_similarity",
+            "same_country",
+            "confidence",
+        ])
+        .unwrap();
+    for m in matches {
+        let (ca, cb) = (&a[m.a as usize].0, &b[m.b as usize].0);
+        let same_country = m.same_country.map_or("".to_string(), |s| s.to_string());
+        writer
+            .write_record([
+                &ca.id,
+                &cb.id,
+                &ca.name,
+                &cb.name,
+                &format!("{:.0}", m.distance_m),
+                &format!("{:.3}", m.name_similarity),
+                &same_country,
+                &format!("{:.3}", m.confidence),
+            ])
+            .unwrap();
+    }
+}
+
+// geocode <input.csv> <output.csv> [k] [balltree|rstar|hprtree]
+fn run_geocode(args: &[String]) {
+    if args.len() < 2 {
+        eprintln!("usage: geocode <input.csv> <output.csv> [k] [balltree|rstar|hprtree]");
+        return;
+    }
+    let (input, output) = (&args[0], &args[1]);
+    let k = args.get(2).map_or(1, |k| k.parse().unwrap());
+    let places = read(
+        b';',
+        "../../data/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
+        140974,
+        geocode::opendata_place,
     );
+    let stime = time::Instant::now();
+    match args.get(3).map_or("balltree", |b| b.as_str()) {
+        "balltree" => geocode::geocode_file::<BallTree<_>>(places, input, output, k),
+        "rstar" => geocode::geocode_file::<RTree<_>>(places, input, output, k),
+        "hprtree" => geocode::geocode_file::<HPRTree<_>>(places, input, output, k),
+        backend => eprintln!("unknown backend {backend}"),
+    }
+    let etime = time::Instant::now();
+    println!("geocode done in {:?}", etime - stime);
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("match") => return run_matching(),
+        Some("geocode") => return run_geocode(&args[1..]),
+        _ => (),
+    }
+
     {
         let mut stdin = io::stdin();
         print!("Press enter to start...");
@@ -934,6 +3041,19 @@ fn main() {
 
     create_dir_all(Path::new("result/querypre/rstar/")).unwrap();
     create_dir_all(Path::new("result/querypre/hprtree/")).unwrap();
+    create_dir_all(Path::new("result/querycount/rstar/")).unwrap();
+    create_dir_all(Path::new("result/queryselectivity/rstar/")).unwrap();
+    create_dir_all(Path::new("result/queryselectivity/hprtree/")).unwrap();
+    for mode in ResultMode::ALL {
+        create_dir_all(Path::new(&format!(
+            "result/querymodes/rstar/{}/",
+            mode.name()
+        )))
+        .unwrap();
+    }
+    for mode in ["count_via_clones", "clones", "ids_via_clones"] {
+        create_dir_all(Path::new(&format!("result/querymodes/hprtree/{mode}/"))).unwrap();
+    }
     create_dir_all(Path::new("result/queryall/rstar/")).unwrap();
     create_dir_all(Path::new("result/queryall/hprtree/")).unwrap();
     create_dir_all(Path::new("result/build/rstar/")).unwrap();
@@ -941,6 +3061,57 @@ fn main() {
     create_dir_all(Path::new("result/build/hprtree/")).unwrap();
     create_dir_all(Path::new("result/build/d_hprtree/")).unwrap();
     create_dir_all(Path::new("result/szfiles/")).unwrap();
+    create_result_dirs(<UniformGrid<Element> as SpatialIndex<Element>>::NAME);
+    create_dir_all(Path::new("result/querypre/grid_fixed/")).unwrap();
+    create_result_dirs(<GeohashIndex<Element> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<LinearScan<Element> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<SortedArray<Element> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<LearnedIndex<Element, Morton> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<LearnedIndex<Element, Hilbert> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<S2Index<Element> as SpatialIndex<Element>>::NAME);
+    create_dir_all(Path::new("result/queryradius/s2/")).unwrap();
+    create_result_dirs(<HexIndex<Element> as SpatialIndex<Element>>::NAME);
+    create_dir_all(Path::new("result/queryknn/hex/")).unwrap();
+    for backend in ["balltree", "hprtree", "rstar"] {
+        create_dir_all(Path::new(&format!("result/queryradius/{backend}/"))).unwrap();
+        create_dir_all(Path::new(&format!("result/queryknn/{backend}/"))).unwrap();
+    }
+    create_result_dirs(<BallTree<Element> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<SlabTree<Element, F32> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<SlabTree<Element, Q32> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<SlabTree<Element, Q16> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<CompressedTree<Element> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<PackedTree<Element, Bfs> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<PackedTree<Element, Dfs> as SpatialIndex<Element>>::NAME);
+    create_result_dirs(<PackedTree<Element, Veb> as SpatialIndex<Element>>::NAME);
+    create_dir_all(Path::new("result/hexbins/")).unwrap();
+    for method in [
+        "rstar",
+        "packed_bfs",
+        "grid",
+        "balltree",
+        "hprtree",
+        "rstar_sync",
+        "planesweep",
+    ] {
+        create_dir_all(Path::new(&format!("result/join/{method}/"))).unwrap();
+    }
+    create_dir_all(Path::new("result/hexops/")).unwrap();
+    for backend in ["rstar", "hprtree", "packed_bfs"] {
+        create_dir_all(Path::new(&format!("result/replay/{backend}/"))).unwrap();
+    }
+    for backend in ["rstar", "balltree", "hprtree", "packed_bfs", "grid"] {
+        create_dir_all(Path::new(&format!("result/replaylog/{backend}/"))).unwrap();
+    }
+    for method in ["topktree", "naive"] {
+        create_dir_all(Path::new(&format!("result/querytopk/{method}/"))).unwrap();
+    }
+    for method in ["aggtree", "rstar", "packed_bfs"] {
+        create_dir_all(Path::new(&format!("result/queryaggregate/{method}/"))).unwrap();
+    }
+    for strategy in ["postfilter", "partitioned", "summarytree"] {
+        create_dir_all(Path::new(&format!("result/queryfilter/{strategy}/"))).unwrap();
+    }
 
     // println!("u64: {}", std::mem::size_of_val(&64u64));
     // println!("u32: {}", std::mem::size_of_val(&64u32));
@@ -1350,11 +3521,120 @@ fn main() {
         ))
     };
 
+    let opendata_to_cityelement = |record: StringRecord| {
+        assert!(record.len() == 20);
+        let geoid = record.get(0).unwrap().parse::<u32>().unwrap();
+        let coords = record
+            .get(record.len() - 1)
+            .unwrap()
+            .split_once(", ")
+            .unwrap();
+        let lat = coords.0.parse::<f32>().unwrap();
+        let lon = coords.1.parse::<f32>().unwrap();
+        let column = |i: usize| record.get(i).unwrap().to_string();
+        // the elevation is often missing, the digital elevation model never
+        let elevation = record
+            .get(14)
+            .unwrap()
+            .parse::<i32>()
+            .or_else(|_| record.get(15).unwrap().parse::<i32>())
+            .ok();
+
+        Some((
+            CityElement {
+                lat,
+                lon,
+                id: geoid,
+                name: column(1),
+                ascii_name: column(2),
+                country: column(6),
+                admin1: column(9),
+                admin2: column(10),
+                population: record.get(13).unwrap().parse::<u64>().unwrap_or(0),
+                timezone: column(16),
+                elevation,
+            },
+            hprtree::Point { x: lon, y: lat },
+        ))
+    };
+    // name and country code like the matching reads them, nothing else
+    let matthe_to_cityelement = |record: StringRecord| {
+        assert!(record.len() == 10);
+
+        let geoid = match record.get(0).unwrap().parse::<u32>() {
+            Ok(data) => data,
+            Err(_) => return None,
+        };
+        let lat = match record.get(8).unwrap().parse::<f32>() {
+            Ok(data) => data,
+            Err(_) => return None,
+        };
+        let lon = match record.get(9).unwrap().parse::<f32>() {
+            Ok(data) => data,
+            Err(_) => return None,
+        };
+        let name = record.get(1).unwrap().to_string();
+
+        Some((
+            CityElement {
+                lat,
+                lon,
+                id: geoid,
+                ascii_name: name.clone(),
+                name,
+                country: record.get(6).unwrap().to_string(),
+                admin1: String::new(),
+                admin2: String::new(),
+                population: 0,
+                timezone: String::new(),
+                elevation: None,
+            },
+            hprtree::Point { x: lon, y: lat },
+        ))
+    };
+    let simplemaps_to_cityelement = |record: StringRecord| {
+        assert!(record.len() == 11);
+        let lat = record.get(2).unwrap().parse::<f32>().unwrap();
+        let lon = record.get(3).unwrap().parse::<f32>().unwrap();
+        let geoid = record.get(10).unwrap().parse::<u32>().unwrap();
+        let column = |i: usize| record.get(i).unwrap().to_string();
+        Some((
+            CityElement {
+                lat,
+                lon,
+                id: geoid,
+                name: column(0),
+                ascii_name: column(1),
+                country: column(5),
+                admin1: column(7),
+                admin2: String::new(),
+                // empty for some cities
+                population: record.get(9).unwrap().parse::<f64>().unwrap_or(0f64) as u64,
+                timezone: String::new(),
+                elevation: None,
+            },
+            hprtree::Point { x: lon, y: lat },
+        ))
+    };
+    let synthetic_to_cityelement =
+        |x: f32, y: f32, id: u32| (CityElement::generated(y, x, id), Point { x, y });
+    let random_to_cityelement = |record: StringRecord| {
+        assert!(record.len() == 3);
+        let lat = record.get(0).unwrap().parse::<f32>().unwrap();
+        let lon = record.get(1).unwrap().parse::<f32>().unwrap();
+        let id = record.get(2).unwrap().parse::<u32>().unwrap();
+        Some((
+            CityElement::generated(lat, lon, id),
+            hprtree::Point { x: lon, y: lat },
+        ))
+    };
+
     println!("{}", std::mem::size_of::<Element>());
     println!("{}", std::mem::size_of::<BiggerElement>());
     println!("{}", std::mem::size_of::<BigElement>());
     println!("{}", std::mem::size_of::<VeryBigElement>());
     println!("{}", std::mem::size_of::<VeryVeryBigElement>());
+    println!("{}", std::mem::size_of::<CityElement>());
     /////
     // println!("{}",std::mem::offset_of!(BiggerElement, lat));
     // println!("{}",std::mem::offset_of!(BiggerElement, lon));
@@ -1424,6 +3704,18 @@ fn main() {
         bench_hprtree_random_uniform(random_to_veryverybigelement);
         bench_hprtree_random_clustered(random_to_veryverybigelement);
         println!("hprtree veryveryelement done\n");
+        ///// hprtree cityelement:
+        bench_hprtree_opendata(opendata_to_cityelement);
+        bench_hprtree_matthe(matthe_to_cityelement);
+        bench_hprtree_simplemaps(simplemaps_to_cityelement);
+        bench_hprtree_synthetic_180x90x_x(1, synthetic_to_cityelement);
+        bench_hprtree_synthetic_180x90x_x(4, synthetic_to_cityelement);
+        bench_hprtree_synthetic_180x90x_x(16, synthetic_to_cityelement);
+        bench_hprtree_synthetic_180x90x_x(64, synthetic_to_cityelement);
+        bench_hprtree_synthetic_180x90x_x(256, synthetic_to_cityelement);
+        bench_hprtree_random_uniform(random_to_cityelement);
+        bench_hprtree_random_clustered(random_to_cityelement);
+        println!("hprtree cityelement done\n");
     }
 
     {
@@ -1488,6 +3780,850 @@ fn main() {
         bench_rstar_random_uniform(random_to_veryverybigelement);
         bench_rstar_random_clustered(random_to_veryverybigelement);
         println!("rstar veryverybigelement done\n");
+        ///// rstar cityelement:
+        bench_rstar_opendata(opendata_to_cityelement);
+        bench_rstar_matthe(matthe_to_cityelement);
+        bench_rstar_simplemaps(simplemaps_to_cityelement);
+        bench_rstar_synthetic_180x90x_x(1, synthetic_to_cityelement);
+        bench_rstar_synthetic_180x90x_x(4, synthetic_to_cityelement);
+        bench_rstar_synthetic_180x90x_x(16, synthetic_to_cityelement);
+        bench_rstar_synthetic_180x90x_x(64, synthetic_to_cityelement);
+        bench_rstar_synthetic_180x90x_x(256, synthetic_to_cityelement);
+        bench_rstar_random_uniform(random_to_cityelement);
+        bench_rstar_random_clustered(random_to_cityelement);
+        println!("rstar cityelement done\n");
+
+        // the string heap the szfile sizes above don't include
+        for_each_dataset(
+            opendata_to_cityelement,
+            matthe_to_cityelement,
+            simplemaps_to_cityelement,
+            synthetic_to_cityelement,
+            random_to_cityelement,
+            |data, dataset, _| {
+                let heap: usize = data.iter().map(|e| e.0.heap_size()).sum();
+                append_szfile("payload_heap", &format!("{dataset}_CityElement: {heap}"));
+            },
+        );
+    }
+
+    {
+        // baselines
+        ///// uniform grid:
+        bench_index_all::<_, UniformGrid<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, UniformGrid<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_index_all::<_, UniformGrid<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        for mult in [1, 4, 16, 64, 256] {
+            bench_grid_synthetic_180x90x_x_fixed(mult, synthetic_to_element);
+        }
+        println!("grid done\n");
+        ///// geohash buckets:
+        bench_index_all::<_, GeohashIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, GeohashIndex<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_index_all::<_, GeohashIndex<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        println!("geohash done\n");
+        ///// linear scan:
+        bench_index_all::<_, LinearScan<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, LinearScan<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_index_all::<_, LinearScan<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        println!("linscan done\n");
+        ///// x-sorted array:
+        bench_index_all::<_, SortedArray<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, SortedArray<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_index_all::<_, SortedArray<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        println!("sortedx done\n");
+    }
+
+    {
+        // learned index
+        ///// morton keys:
+        bench_index_all::<_, LearnedIndex<_, Morton>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, LearnedIndex<_, Morton>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_index_all::<_, LearnedIndex<_, Morton>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        println!("learned morton done\n");
+        ///// hilbert keys:
+        bench_index_all::<_, LearnedIndex<_, Hilbert>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, LearnedIndex<_, Hilbert>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_index_all::<_, LearnedIndex<_, Hilbert>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        println!("learned hilbert done\n");
+    }
+
+    {
+        // spherical cells
+        bench_index_all::<_, S2Index<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, S2Index<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        ///// radius queries, compared with the hprtree filtering its bounding box results:
+        bench_queryradius_all::<_, S2Index<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_queryradius_all::<_, HPRTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        println!("s2 done\n");
+    }
+
+    {
+        // hexagonal cells
+        bench_index_all::<_, HexIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, HexIndex<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_queryknn_all::<_, HexIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        for_each_dataset(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+            |data, dataset, envelopes| {
+                let res = hex::auto_resolution(data.len());
+                if ["opendata", "matthe", "simplemaps"].contains(&dataset) {
+                    write_hex_bins(&HexIndex::with_resolution(data, res), dataset);
+                }
+                bench_hexops(envelopes, res);
+            },
+        );
+        println!("hex done\n");
+    }
+
+    {
+        // metric tree
+        bench_index_all::<_, BallTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        ///// radius and knn under haversine, against the trees filtering bounding boxes:
+        bench_metric_all::<_, BallTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+        );
+        // the hprtree radius series were written with s2 already, only knn here
+        for_each_city_dataset(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            |data, _, envelopes| {
+                let index: HPRTree<_> = SpatialIndex::build(data);
+                bench_queryknn_index(envelopes, &index);
+            },
+        );
+        bench_metric_all::<_, RTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+        );
+        println!("balltree done\n");
+    }
+
+    {
+        // node layouts of a packed tree
+        bench_querypre_layouts(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querypre_layouts(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        println!("layouts done\n");
+    }
+
+    {
+        // coordinates apart from the payloads, compare with the hprtree runs above
+        bench_storage_modes(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_storage_modes(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        bench_storage_modes(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_storage_modes(
+            opendata_to_verybigelement,
+            matthe_to_verybigelement,
+            simplemaps_to_verybigelement,
+            synthetic_to_verybigelement,
+            random_to_verybigelement,
+        );
+        bench_storage_modes(
+            opendata_to_veryverybigelement,
+            matthe_to_veryverybigelement,
+            simplemaps_to_veryverybigelement,
+            synthetic_to_veryverybigelement,
+            random_to_veryverybigelement,
+        );
+        println!("storage modes done\n");
+    }
+
+    {
+        // bit packed leaves, the szfiles sit next to the hprtree and rstar ones
+        bench_index_all::<_, CompressedTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_index_all::<_, CompressedTree<_>>(
+            opendata_to_biggerelement,
+            matthe_to_biggerelement,
+            simplemaps_to_biggerelement,
+            synthetic_to_biggerelement,
+            random_to_biggerelement,
+        );
+        println!("compressed done\n");
+    }
+
+    {
+        // node visits and comparisons per querypre envelope, the layouts all count the same
+        bench_querycount_all::<_, RTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, UniformGrid<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, GeohashIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, LinearScan<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, SortedArray<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, LearnedIndex<_, Morton>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, LearnedIndex<_, Hilbert>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, S2Index<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, HexIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, BallTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, PackedTree<_, Bfs>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, SlabTree<_, F32>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, SlabTree<_, Q32>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, SlabTree<_, Q16>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querycount_all::<_, CompressedTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        println!("querycount done\n");
+    }
+
+    {
+        // the same fractions of every dataset instead of the same counts
+        bench_queryselectivity_all::<_, RTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_queryselectivity_all::<_, HPRTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_queryselectivity_all::<_, PackedTree<_, Bfs>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_queryselectivity_all::<_, UniformGrid<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        println!("queryselectivity done\n");
+    }
+
+    {
+        // result modes, with the smallest and a large payload to see what the copies cost
+        bench_querymodes_all::<_, RTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, RTree<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, PackedTree<_, Bfs>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, PackedTree<_, Bfs>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, SlabTree<_, F32>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, SlabTree<_, F32>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, BallTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, BallTree<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, UniformGrid<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, UniformGrid<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, LinearScan<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, LinearScan<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, GeohashIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, GeohashIndex<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, S2Index<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, S2Index<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, HexIndex<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, HexIndex<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, LearnedIndex<_, Morton>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, LearnedIndex<_, Morton>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, LearnedIndex<_, Hilbert>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, LearnedIndex<_, Hilbert>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, CompressedTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, CompressedTree<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_all::<_, SortedArray<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_all::<_, SortedArray<_>>(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        bench_querymodes_hprtree_all(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+        );
+        bench_querymodes_hprtree_all(
+            opendata_to_bigelement,
+            matthe_to_bigelement,
+            simplemaps_to_bigelement,
+            synthetic_to_bigelement,
+            random_to_bigelement,
+        );
+        println!("querymodes done\n");
+    }
+
+    {
+        // spatial joins, the dedup pipeline pairs the city datasets like this every night
+        let mut cities = Vec::new();
+        for_each_city_dataset(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            |data, dataset, _| cities.push((dataset.to_string(), data)),
+        );
+        let preds = [
+            JoinPredicate::Distance(1000f64),
+            JoinPredicate::Box(0.01f32),
+        ];
+        let (opendata, matthe) = (&cities[0], &cities[1]);
+        for pred in preds {
+            let name = format!("{}-{}.{}", opendata.0, matthe.0, pred.name());
+            bench_join(&opendata.1, &matthe.1, &name, pred);
+        }
+        drop(cities);
+
+        // clustered against uniform, the sets paired up by size
+        let mut uniform = random_datasets("../../data/new/uniform/", false);
+        let mut clustered = random_datasets("../../data/new/clustered/", true);
+        uniform.sort_by_key(|d| d.2);
+        clustered.sort_by_key(|d| d.2);
+        for (c, u) in clustered.iter().zip(&uniform) {
+            let a = read(b',', &c.0, c.2, random_to_element);
+            let b = read(b',', &u.0, u.2, random_to_element);
+            for pred in preds {
+                let name = format!("clustered_{}-uniform_{}.{}", c.1, u.1, pred.name());
+                bench_join(&a, &b, &name, pred);
+            }
+        }
+        println!("join done\n");
+    }
+
+    {
+        // spatial queries that also filter on population and country
+        bench_queryfilter_all(opendata_to_cityelement, simplemaps_to_cityelement);
+        println!("queryfilter done\n");
+    }
+
+    {
+        // how many cities and how many people inside the envelopes, without the cities
+        bench_queryaggregate_all(
+            opendata_to_cityelement,
+            matthe_to_cityelement,
+            simplemaps_to_cityelement,
+            synthetic_to_cityelement,
+            random_to_cityelement,
+        );
+        println!("queryaggregate done\n");
+    }
+
+    {
+        // the most populous and the highest cities in the envelopes. matthe has neither column,
+        // simplemaps no elevation.
+        for_each_populated_city_dataset(
+            opendata_to_cityelement,
+            simplemaps_to_cityelement,
+            |data, dataset, envelopes| {
+                if dataset == "opendata" {
+                    bench_querytopk::<_, ByElevation>(data.clone(), &envelopes);
+                }
+                bench_querytopk::<_, ByPopulation>(data, &envelopes);
+            },
+        );
+        println!("querytopk done\n");
+    }
+
+    {
+        // skewed query streams instead of the round robin over the querypre envelopes
+        let workloads: Vec<Centres> = args
+            .windows(2)
+            .filter(|w| w[0] == "--workload")
+            .map(|w| Centres::parse(&w[1]).expect("bad workload"))
+            .collect();
+        let workloads = if workloads.is_empty() {
+            default_workloads()
+        } else {
+            workloads
+        };
+        for_each_city_dataset(
+            opendata_to_cityelement,
+            matthe_to_cityelement,
+            simplemaps_to_cityelement,
+            |data, _, envelopes| bench_replay(data, &envelopes, &workloads),
+        );
+        println!("replay done\n");
+    }
+
+    {
+        // query logs, the envelope files and whatever traces are in data/querylogs, at max
+        // speed or with --pace recorded at the rate they were recorded
+        let pace = args
+            .windows(2)
+            .find(|w| w[0] == "--pace")
+            .map_or(Pace::Max, |w| Pace::parse(&w[1]).expect("bad pace"));
+        bench_replaylog_all::<_, RTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+            pace,
+        );
+        bench_replaylog_all::<_, BallTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+            pace,
+        );
+        bench_replaylog_all::<_, HPRTree<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+            pace,
+        );
+        bench_replaylog_all::<_, PackedTree<_, Bfs>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+            pace,
+        );
+        bench_replaylog_all::<_, UniformGrid<_>>(
+            opendata_to_element,
+            matthe_to_element,
+            simplemaps_to_element,
+            synthetic_to_element,
+            random_to_element,
+            pace,
+        );
+        println!("replaylog done\n");
     }
 
     let program_end = time::Instant::now();
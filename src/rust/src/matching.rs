@@ -0,0 +1,279 @@
+use csv::StringRecord;
+use hprtree::Point;
+
+use crate::index::{Located, SpatialIndex};
+use crate::join::{index_nested_loop, JoinPredicate};
+use crate::packed::{Bfs, PackedTree};
+use crate::sphere::haversine_m;
+
+// records further apart than this are never the same city
+pub const MATCH_RADIUS_M: f64 = 10_000f64;
+// pairs below this are not matched
+pub const MIN_CONFIDENCE: f64 = 0.75;
+
+// weights of the confidence, they add up to 1
+const NAME_WEIGHT: f64 = 0.6;
+const DISTANCE_WEIGHT: f64 = 0.25;
+const COUNTRY_WEIGHT: f64 = 0.15;
+
+// one row of a city source, with what the matching compares
+#[derive(Clone, Debug)]
+pub struct City {
+    // position in the source, set by number_cities
+    pub idx: u32,
+    pub id: String,
+    pub name: String,
+    // normalized name
+    key: String,
+    // iso 3166 alpha-2, None if the source doesn't have a plausible one
+    country: Option<String>,
+    lat: f32,
+    lon: f32,
+}
+
+impl Located for City {
+    fn location(&self) -> Point {
+        Point {
+            x: self.lon,
+            y: self.lat,
+        }
+    }
+}
+
+fn city(id: &str, name: &str, country: &str, lat: f32, lon: f32) -> Option<(City, Point)> {
+    let country = country.trim().to_ascii_uppercase();
+    let country = if country.len() == 2 && country.bytes().all(|b| b.is_ascii_uppercase()) {
+        Some(country)
+    } else {
+        None
+    };
+    Some((
+        City {
+            idx: 0,
+            id: id.to_string(),
+            name: name.to_string(),
+            key: normalize(name),
+            country,
+            lat,
+            lon,
+        },
+        Point { x: lon, y: lat },
+    ))
+}
+
+// column layouts like the element deserializers in main.rs read them. opendatasoft and
+// simplemaps have ascii names next to the real ones, those are compared.
+pub fn opendata_city(record: StringRecord) -> Option<(City, Point)> {
+    assert!(record.len() == 20);
+    let (lat, lon) = record.get(record.len() - 1)?.split_once(", ")?;
+    city(
+        record.get(0)?,
+        record.get(2)?,
+        record.get(6)?,
+        lat.parse().ok()?,
+        lon.parse().ok()?,
+    )
+}
+
+// id, city name, ..., country code in column 6, ..., latitude, longitude
+pub fn matthe_city(record: StringRecord) -> Option<(City, Point)> {
+    assert!(record.len() == 10);
+    city(
+        record.get(0)?,
+        record.get(1)?,
+        record.get(6)?,
+        record.get(8)?.parse().ok()?,
+        record.get(9)?.parse().ok()?,
+    )
+}
+
+pub fn simplemaps_city(record: StringRecord) -> Option<(City, Point)> {
+    assert!(record.len() == 11);
+    city(
+        record.get(10)?,
+        record.get(1)?,
+        record.get(5)?,
+        record.get(2)?.parse().ok()?,
+        record.get(3)?.parse().ok()?,
+    )
+}
+
+pub fn number_cities(cities: &mut [(City, Point)]) {
+    for (i, c) in cities.iter_mut().enumerate() {
+        c.0.idx = i as u32;
+    }
+}
+
+// lowercase, punctuation to spaces, single spaces and the usual "saint" spellings folded
+pub fn normalize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_lowercase().next().unwrap()
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    cleaned
+        .split_whitespace()
+        .map(|w| match w {
+            "st" | "ste" | "sainte" => "saint",
+            w => w,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// jaro-winkler similarity in [0, 1], 1 for equal strings
+pub fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() || b.is_empty() {
+        return if a.len() == b.len() { 1f64 } else { 0f64 };
+    }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut a_matched = vec![false; a.len()];
+    let mut b_matched = vec![false; b.len()];
+    let mut matches = 0;
+    for (i, ca) in a.iter().enumerate() {
+        let from = i.saturating_sub(window);
+        let to = (i + window + 1).min(b.len());
+        for j in from..to {
+            if !b_matched[j] && b[j] == *ca {
+                a_matched[i] = true;
+                b_matched[j] = true;
+                matches += 1;
+                break;
+            }
+        }
+    }
+    if matches == 0 {
+        return 0f64;
+    }
+    let mut transpositions = 0;
+    let mut j = 0;
+    for (i, ca) in a.iter().enumerate() {
+        if !a_matched[i] {
+            continue;
+        }
+        while !b_matched[j] {
+            j += 1;
+        }
+        if *ca != b[j] {
+            transpositions += 1;
+        }
+        j += 1;
+    }
+    let m = matches as f64;
+    let jaro =
+        (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64 / 2f64) / m) / 3f64;
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count();
+    jaro + prefix as f64 * 0.1 * (1f64 - jaro)
+}
+
+pub struct Match {
+    pub a: u32,
+    pub b: u32,
+    pub distance_m: f64,
+    pub name_similarity: f64,
+    // None if one of the records has no country
+    pub same_country: Option<bool>,
+    pub confidence: f64,
+}
+
+fn score(a: &City, b: &City) -> Match {
+    let distance_m = haversine_m(&a.location(), &b.location());
+    let name_similarity = jaro_winkler(&a.key, &b.key);
+    let same_country = match (&a.country, &b.country) {
+        (Some(x), Some(y)) => Some(x == y),
+        _ => None,
+    };
+    let country = match same_country {
+        Some(true) => 1f64,
+        Some(false) => 0f64,
+        // unknown is neither for nor against
+        None => 0.5f64,
+    };
+    let confidence = NAME_WEIGHT * name_similarity
+        + DISTANCE_WEIGHT * (1f64 - distance_m / MATCH_RADIUS_M).max(0f64)
+        + COUNTRY_WEIGHT * country;
+    Match {
+        a: a.idx,
+        b: b.idx,
+        distance_m,
+        name_similarity,
+        same_country,
+        confidence,
+    }
+}
+
+// index nested loop join of a against b within MATCH_RADIUS_M, then the best pairs first with
+// every record matched at most once
+pub fn match_sources(a: &[(City, Point)], b: &[(City, Point)]) -> Vec<Match> {
+    let index = PackedTree::<City, Bfs>::build(b.to_vec());
+    let mut candidates = Vec::new();
+    index_nested_loop(
+        a,
+        &index,
+        JoinPredicate::Distance(MATCH_RADIUS_M),
+        |ca, cb| {
+            let m = score(ca, cb);
+            if m.confidence >= MIN_CONFIDENCE {
+                candidates.push(m);
+            }
+        },
+    );
+    candidates.sort_by(|x, y| y.confidence.total_cmp(&x.confidence));
+    let mut a_used = vec![false; a.len()];
+    let mut b_used = vec![false; b.len()];
+    candidates.retain(|m| {
+        let (i, j) = (m.a as usize, m.b as usize);
+        if a_used[i] || b_used[j] {
+            return false;
+        }
+        a_used[i] = true;
+        b_used[j] = true;
+        true
+    });
+    candidates
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// groups records connected by matches, records are numbered source after source. a group with
+// two records of the same source means the pairwise matches disagree.
+pub fn merge(sizes: &[usize], matches: &[(usize, usize, Vec<Match>)]) -> Vec<Vec<(usize, u32)>> {
+    let offsets: Vec<usize> = sizes
+        .iter()
+        .scan(0, |sum, &n| {
+            *sum += n;
+            Some(*sum - n)
+        })
+        .collect();
+    let total = sizes.iter().sum();
+    let mut parent: Vec<usize> = (0..total).collect();
+    for (sa, sb, pairs) in matches {
+        for m in pairs {
+            let x = find(&mut parent, offsets[*sa] + m.a as usize);
+            let y = find(&mut parent, offsets[*sb] + m.b as usize);
+            parent[x] = y;
+        }
+    }
+    let mut groups: Vec<Vec<(usize, u32)>> = vec![Vec::new(); total];
+    for (s, &n) in sizes.iter().enumerate() {
+        for i in 0..n {
+            let root = find(&mut parent, offsets[s] + i);
+            groups[root].push((s, i as u32));
+        }
+    }
+    groups.retain(|g| !g.is_empty());
+    groups
+}
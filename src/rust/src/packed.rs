@@ -0,0 +1,431 @@
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use hprtree::{BBox, Point};
+
+use crate::index::{clone_bbox, extent_of, Counter, SpatialIndex, VisitQuery};
+use crate::learned::hilbert;
+use crate::quality::TreeQuality;
+
+// entries per node, the same as the hprtree default
+pub const NODE_CAPACITY: usize = 16;
+// bits per dimension for the hilbert sort
+const HILBERT_BITS: u32 = 16;
+
+pub enum Order {
+    // level by level, root first, the order the tree is built in
+    BreadthFirst,
+    // preorder, every subtree is one contiguous run
+    DepthFirst,
+    // van emde boas: the top half of the levels first, then every bottom subtree, recursively
+    VanEmdeBoas,
+}
+
+// how the nodes of a packed tree are arranged in memory, the tree itself stays the same
+pub trait Layout {
+    const INDEX_NAME: &'static str;
+    const ORDER: Order;
+}
+
+pub struct Bfs;
+pub struct Dfs;
+pub struct Veb;
+
+impl Layout for Bfs {
+    const INDEX_NAME: &'static str = "packed_bfs";
+    const ORDER: Order = Order::BreadthFirst;
+}
+
+impl Layout for Dfs {
+    const INDEX_NAME: &'static str = "packed_dfs";
+    const ORDER: Order = Order::DepthFirst;
+}
+
+impl Layout for Veb {
+    const INDEX_NAME: &'static str = "packed_veb";
+    const ORDER: Order = Order::VanEmdeBoas;
+}
+
+// one node with the boxes of all its entries inline, so a visit touches one block of memory.
+// children are node indices, or item indices in leaves.
+#[derive(Clone)]
+struct Node {
+    len: u8,
+    leaf: bool,
+    boxes: [[f32; 4]; NODE_CAPACITY],
+    children: [u32; NODE_CAPACITY],
+}
+
+impl Node {
+    fn empty(leaf: bool) -> Self {
+        Node {
+            len: 0,
+            leaf,
+            boxes: [[0f32; 4]; NODE_CAPACITY],
+            children: [0; NODE_CAPACITY],
+        }
+    }
+
+    fn push(&mut self, b: [f32; 4], child: u32) {
+        self.boxes[self.len as usize] = b;
+        self.children[self.len as usize] = child;
+        self.len += 1;
+    }
+
+    fn bounds(&self) -> [f32; 4] {
+        let mut b = [
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ];
+        for e in &self.boxes[..self.len as usize] {
+            b[0] = b[0].min(e[0]);
+            b[1] = b[1].max(e[1]);
+            b[2] = b[2].min(e[2]);
+            b[3] = b[3].max(e[3]);
+        }
+        b
+    }
+
+    fn children(&self) -> &[u32] {
+        &self.children[..self.len as usize]
+    }
+}
+
+// points and elements sorted by the hilbert key of the points within extent
+pub fn hilbert_order<T>(data: Vec<(T, Point)>, extent: &BBox) -> (Vec<Point>, Vec<T>) {
+    let cells = ((1u64 << HILBERT_BITS) - 1) as f64;
+    let quantize = |v: f32, min: f32, max: f32| {
+        if max <= min {
+            0
+        } else {
+            ((v as f64 - min as f64) / (max as f64 - min as f64) * cells) as u64
+        }
+    };
+    let mut keyed: Vec<(u64, T, Point)> = data
+        .into_iter()
+        .map(|(e, p)| {
+            let x = quantize(p.x, extent.minx, extent.maxx);
+            let y = quantize(p.y, extent.miny, extent.maxy);
+            (hilbert(x, y, HILBERT_BITS), e, p)
+        })
+        .collect();
+    keyed.sort_by_key(|e| e.0);
+
+    let mut points = Vec::with_capacity(keyed.len());
+    let mut elements = Vec::with_capacity(keyed.len());
+    for (_, e, p) in keyed {
+        points.push(p);
+        elements.push(e);
+    }
+    (points, elements)
+}
+
+// static r-tree packed bottom up from hilbert sorted points, like the hprtree
+pub struct PackedTree<T, L> {
+    nodes: Vec<Node>,
+    root: u32,
+    // levels, leaves count as one
+    height: usize,
+    elements: Vec<T>,
+    extent: BBox,
+    layout: PhantomData<L>,
+}
+
+impl<T, L> PackedTree<T, L>
+where
+    L: Layout,
+{
+    // the same tree stored in another layout, without building it again
+    pub fn with_layout<L2: Layout>(self) -> PackedTree<T, L2> {
+        let mut tree = PackedTree {
+            nodes: self.nodes,
+            root: self.root,
+            height: self.height,
+            elements: self.elements,
+            extent: self.extent,
+            layout: PhantomData,
+        };
+        tree.arrange();
+        tree
+    }
+
+    // position i of the new layout gets the node that is at order[i] now
+    fn arrange(&mut self) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut order = Vec::with_capacity(self.nodes.len());
+        match L::ORDER {
+            Order::BreadthFirst => {
+                let mut level = vec![self.root];
+                while !level.is_empty() {
+                    order.extend_from_slice(&level);
+                    level = level
+                        .iter()
+                        .filter(|&&n| !self.nodes[n as usize].leaf)
+                        .flat_map(|&n| self.nodes[n as usize].children().iter().copied())
+                        .collect();
+                }
+            }
+            Order::DepthFirst => {
+                let mut stack = vec![self.root];
+                while let Some(n) = stack.pop() {
+                    order.push(n);
+                    let node = &self.nodes[n as usize];
+                    if !node.leaf {
+                        stack.extend(node.children().iter().rev());
+                    }
+                }
+            }
+            Order::VanEmdeBoas => self.veb(self.root, self.height, &mut order),
+        }
+
+        let mut position = vec![0u32; self.nodes.len()];
+        for (new, &old) in order.iter().enumerate() {
+            position[old as usize] = new as u32;
+        }
+        let mut nodes: Vec<Node> = order
+            .iter()
+            .map(|&old| self.nodes[old as usize].clone())
+            .collect();
+        for node in nodes.iter_mut().filter(|n| !n.leaf) {
+            for child in &mut node.children[..node.len as usize] {
+                *child = position[*child as usize];
+            }
+        }
+        self.root = position[self.root as usize];
+        self.nodes = nodes;
+    }
+
+    // emits the nodes of the subtree below root that are less than height levels deep
+    fn veb(&self, root: u32, height: usize, order: &mut Vec<u32>) {
+        if height == 1 {
+            order.push(root);
+            return;
+        }
+        let top = height / 2;
+        self.veb(root, top, order);
+        let mut bottom_roots = vec![root];
+        for _ in 0..top {
+            bottom_roots = bottom_roots
+                .iter()
+                .flat_map(|&n| self.nodes[n as usize].children().iter().copied())
+                .collect();
+        }
+        for r in bottom_roots {
+            self.veb(r, height - top, order);
+        }
+    }
+}
+
+impl<T, L> SpatialIndex<T> for PackedTree<T, L>
+where
+    T: Clone,
+    L: Layout,
+{
+    const NAME: &'static str = L::INDEX_NAME;
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let (points, elements) = hilbert_order(data, &extent);
+
+        // levels bottom up, every level is appended to nodes so the root ends up last
+        let mut nodes = Vec::new();
+        let mut level: Vec<u32> = Vec::new();
+        for (c, chunk) in points.chunks(NODE_CAPACITY).enumerate() {
+            let mut node = Node::empty(true);
+            for (i, p) in chunk.iter().enumerate() {
+                node.push([p.x, p.x, p.y, p.y], (c * NODE_CAPACITY + i) as u32);
+            }
+            level.push(nodes.len() as u32);
+            nodes.push(node);
+        }
+        let mut height = if level.is_empty() { 0 } else { 1 };
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(NODE_CAPACITY));
+            for chunk in level.chunks(NODE_CAPACITY) {
+                let mut node = Node::empty(false);
+                for &child in chunk {
+                    node.push(nodes[child as usize].bounds(), child);
+                }
+                next.push(nodes.len() as u32);
+                nodes.push(node);
+            }
+            level = next;
+            height += 1;
+        }
+
+        let mut tree = PackedTree {
+            root: nodes.len().saturating_sub(1) as u32,
+            nodes,
+            height,
+            elements,
+            extent,
+            layout: PhantomData,
+        };
+        tree.arrange();
+        tree
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.nodes.capacity() * std::mem::size_of::<Node>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+    }
+    fn quality(&self) -> Option<TreeQuality> {
+        let mut quality = TreeQuality::default();
+        if self.nodes.is_empty() {
+            return Some(quality);
+        }
+        let mut stack = vec![(self.root, 0)];
+        while let Some((n, level)) = stack.pop() {
+            let node = &self.nodes[n as usize];
+            if node.leaf {
+                quality.add_leaf(level, &node.bounds(), node.len as usize, NODE_CAPACITY);
+            } else {
+                quality.add_node(
+                    level,
+                    &node.bounds(),
+                    &node.boxes[..node.len as usize],
+                    NODE_CAPACITY,
+                );
+                stack.extend(node.children().iter().map(|&c| (c, level + 1)));
+            }
+        }
+        Some(quality)
+    }
+}
+
+impl<T, L> VisitQuery<T> for PackedTree<T, L>
+where
+    L: Layout,
+{
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n as usize];
+            counter.nodes(1);
+            // leaf boxes are the points themselves
+            if node.leaf {
+                counter.entries(node.len as usize);
+            } else {
+                counter.bbox_tests(node.len as usize);
+            }
+            for (b, &child) in node.boxes[..node.len as usize].iter().zip(node.children()) {
+                if b[0] <= env.maxx && b[1] >= env.minx && b[2] <= env.maxy && b[3] >= env.miny {
+                    if node.leaf {
+                        visit(&self.elements[child as usize]);
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// what a level tree keeps per node, the box and whatever the queries prune by
+pub trait NodeSummary: Copy {
+    fn bbox(&self) -> &[f32; 4];
+    fn combine(children: &[Self]) -> Self;
+}
+
+// hilbert packed tree stored as levels of summaries, for trees whose nodes carry more than a
+// box. leaves first, node i of a level has the nodes [i * NODE_CAPACITY, (i + 1) * NODE_CAPACITY)
+// of the level below as children, leaf i the items in that range.
+pub struct LevelTree<S, E> {
+    pub levels: Vec<Vec<S>>,
+    pub points: Vec<Point>,
+    pub items: Vec<E>,
+}
+
+impl<S, E> LevelTree<S, E>
+where
+    S: NodeSummary,
+{
+    // entry gives the summary of a single item, leaves combine those of their items
+    pub fn build<F>(data: Vec<(E, Point)>, entry: F) -> Self
+    where
+        F: Fn(&Point, &E) -> S,
+    {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let (points, items) = hilbert_order(data, &extent);
+        let mut level: Vec<S> = points
+            .chunks(NODE_CAPACITY)
+            .zip(items.chunks(NODE_CAPACITY))
+            .map(|(ps, es)| {
+                S::combine(
+                    &ps.iter()
+                        .zip(es)
+                        .map(|(p, e)| entry(p, e))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let mut levels = Vec::new();
+        while level.len() > 1 {
+            let next = level.chunks(NODE_CAPACITY).map(S::combine).collect();
+            levels.push(level);
+            level = next;
+        }
+        if !level.is_empty() {
+            levels.push(level);
+        }
+        LevelTree {
+            levels,
+            points,
+            items,
+        }
+    }
+
+    // level and position of the root, None if empty
+    pub fn root(&self) -> Option<(usize, usize)> {
+        (!self.levels.is_empty()).then(|| (self.levels.len() - 1, 0))
+    }
+
+    // the children of node n on level, nodes of the level below or items for leaves
+    pub fn children(&self, level: usize, n: usize) -> Range<usize> {
+        let from = n * NODE_CAPACITY;
+        let len = match level {
+            0 => self.items.len(),
+            _ => self.levels[level - 1].len(),
+        };
+        from..(from + NODE_CAPACITY).min(len)
+    }
+
+    // all items below node n on level
+    pub fn items_below(&self, level: usize, n: usize) -> Range<usize> {
+        let span = NODE_CAPACITY.pow(level as u32 + 1);
+        n * span..((n + 1) * span).min(self.items.len())
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.levels.capacity() * std::mem::size_of::<Vec<S>>()
+            + self
+                .levels
+                .iter()
+                .map(|l| l.capacity() * std::mem::size_of::<S>())
+                .sum::<usize>()
+            + self.points.capacity() * std::mem::size_of::<Point>()
+            + self.items.capacity() * std::mem::size_of::<E>()
+    }
+}
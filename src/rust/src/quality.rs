@@ -0,0 +1,181 @@
+use std::fmt;
+
+use rstar::{DefaultParams, ParentNode, RTree, RTreeNode, RTreeObject, RTreeParams, AABB};
+
+// [minx, maxx, miny, maxy] like the packed trees store them
+pub type Rect = [f32; 4];
+
+#[derive(Default)]
+struct LevelStats {
+    nodes: usize,
+    leaves: usize,
+    entries: usize,
+}
+
+// structural metrics of a built tree, filled node by node. levels count from the root (0),
+// areas are in square degrees. the data is points, so leaf entries have no area.
+#[derive(Default)]
+pub struct TreeQuality {
+    levels: Vec<LevelStats>,
+    // pairwise intersection area of sibling boxes
+    overlap: f64,
+    // area of the node boxes not covered by any of their children
+    dead_space: f64,
+    fill_sum: f64,
+    fill_nodes: usize,
+    min_fill: Option<f64>,
+}
+
+fn area(r: &Rect) -> f64 {
+    (r[1] as f64 - r[0] as f64).max(0f64) * (r[3] as f64 - r[2] as f64).max(0f64)
+}
+
+// exact area of the union, strip by strip between the distinct x coordinates
+fn union_area(rects: &[Rect]) -> f64 {
+    let rects: Vec<&Rect> = rects.iter().filter(|r| area(r) > 0f64).collect();
+    let mut xs: Vec<f32> = rects.iter().flat_map(|r| [r[0], r[1]]).collect();
+    xs.sort_by(|a, b| a.total_cmp(b));
+    xs.dedup();
+    let mut total = 0f64;
+    for w in xs.windows(2) {
+        let mut ys: Vec<(f32, f32)> = rects
+            .iter()
+            .filter(|r| r[0] <= w[0] && r[1] >= w[1])
+            .map(|r| (r[2], r[3]))
+            .collect();
+        ys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut covered = 0f64;
+        let mut current: Option<(f32, f32)> = None;
+        for (lo, hi) in ys {
+            current = match current {
+                Some((clo, chi)) if lo <= chi => Some((clo, chi.max(hi))),
+                Some((clo, chi)) => {
+                    covered += chi as f64 - clo as f64;
+                    Some((lo, hi))
+                }
+                None => Some((lo, hi)),
+            };
+        }
+        if let Some((clo, chi)) = current {
+            covered += chi as f64 - clo as f64;
+        }
+        total += (w[1] as f64 - w[0] as f64) * covered;
+    }
+    total
+}
+
+impl TreeQuality {
+    fn level(&mut self, level: usize) -> &mut LevelStats {
+        if self.levels.len() <= level {
+            self.levels.resize_with(level + 1, LevelStats::default);
+        }
+        &mut self.levels[level]
+    }
+
+    fn fill(&mut self, level: usize, entries: usize, capacity: usize) {
+        // the root can't be filled at will, so it does not count
+        if level > 0 {
+            let fill = entries as f64 / capacity as f64;
+            self.fill_sum += fill;
+            self.fill_nodes += 1;
+            self.min_fill = Some(self.min_fill.map_or(fill, |m| m.min(fill)));
+        }
+    }
+
+    // inner node with the boxes of its children
+    pub fn add_node(&mut self, level: usize, bbox: &Rect, children: &[Rect], capacity: usize) {
+        let stats = self.level(level);
+        stats.nodes += 1;
+        stats.entries += children.len();
+        for (i, a) in children.iter().enumerate() {
+            for b in &children[i + 1..] {
+                self.overlap += area(&[
+                    a[0].max(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].min(b[3]),
+                ]);
+            }
+        }
+        self.dead_space += (area(bbox) - union_area(children)).max(0f64);
+        self.fill(level, children.len(), capacity);
+    }
+
+    // leaf with its points, all of its area is dead space
+    pub fn add_leaf(&mut self, level: usize, bbox: &Rect, entries: usize, capacity: usize) {
+        let stats = self.level(level);
+        stats.nodes += 1;
+        stats.leaves += 1;
+        stats.entries += entries;
+        self.dead_space += area(bbox);
+        self.fill(level, entries, capacity);
+    }
+}
+
+// depth, overlap, dead space, average and min fill, then nodes/leaves/entries of every level
+impl fmt::Display for TreeQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let avg_fill = if self.fill_nodes == 0 {
+            1f64
+        } else {
+            self.fill_sum / self.fill_nodes as f64
+        };
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.levels.len(),
+            self.overlap,
+            self.dead_space,
+            avg_fill,
+            self.min_fill.unwrap_or(1f64)
+        )?;
+        for l in &self.levels {
+            write!(f, " {}/{}/{}", l.nodes, l.leaves, l.entries)?;
+        }
+        Ok(())
+    }
+}
+
+fn aabb_rect(env: &AABB<[f32; 2]>) -> Rect {
+    [
+        env.lower()[0],
+        env.upper()[0],
+        env.lower()[1],
+        env.upper()[1],
+    ]
+}
+
+fn rtree_node_quality<T>(node: &ParentNode<T>, level: usize, quality: &mut TreeQuality)
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    let capacity = <DefaultParams as RTreeParams>::MAX_SIZE;
+    let bbox = aabb_rect(&node.envelope());
+    let mut children = Vec::with_capacity(node.children().len());
+    let mut entries = 0;
+    for child in node.children() {
+        match child {
+            RTreeNode::Leaf(_) => entries += 1,
+            RTreeNode::Parent(subparent) => {
+                children.push(aabb_rect(&subparent.envelope()));
+                rtree_node_quality(subparent, level + 1, quality);
+            }
+        }
+    }
+    // bulk loading never mixes leaves and parents in one node
+    if children.is_empty() {
+        quality.add_leaf(level, &bbox, entries, capacity);
+    } else {
+        quality.add_node(level, &bbox, &children, capacity);
+    }
+}
+
+// walks the nodes like size_in_bytes_helper does
+pub fn rtree_quality<T>(tree: &RTree<T>) -> TreeQuality
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>>,
+{
+    let mut quality = TreeQuality::default();
+    rtree_node_quality(tree.root(), 0, &mut quality);
+    quality
+}
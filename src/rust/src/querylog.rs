@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use csv::StringRecord;
+use hprtree::{BBox, HPRTree, Point};
+use rstar::{RTree, RTreeObject, AABB};
+
+use crate::balltree::BallTree;
+use crate::grid::UniformGrid;
+use crate::hex::HexIndex;
+use crate::index::{KnnQuery, Located, RadiusQuery, SpatialIndex, VisitQuery};
+use crate::packed::{Layout, PackedTree};
+use crate::s2::S2Index;
+
+// one query of a log. a log is a csv without header, '#' lines are comments, one query per line:
+//   <micros>,envelope,<minx>,<maxx>,<miny>,<maxy>,[expected]
+//   <micros>,count,<minx>,<maxx>,<miny>,<maxy>,[expected]
+//   <micros>,knn,<lon>,<lat>,<k>,[expected]
+//   <micros>,radius,<lon>,<lat>,<meters>,[expected]
+// micros is the time since the start of the log, expected the number of results (the count
+// for count queries) and may be left empty.
+pub enum Query {
+    Envelope(BBox),
+    Count(BBox),
+    Knn(Point, usize),
+    Radius(Point, f64),
+}
+
+pub struct LoggedQuery {
+    pub at: Duration,
+    pub query: Query,
+    pub expected: Option<usize>,
+}
+
+// how fast a log is replayed
+#[derive(Clone, Copy)]
+pub enum Pace {
+    // every query right after the one before
+    Max,
+    // every query at its timestamp, or right away if the replay fell behind
+    Recorded,
+}
+
+impl Pace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pace::Max => "max",
+            Pace::Recorded => "recorded",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "max" => Some(Pace::Max),
+            "recorded" => Some(Pace::Recorded),
+            _ => None,
+        }
+    }
+}
+
+fn parse_bbox(record: &StringRecord) -> Option<BBox> {
+    Some(BBox {
+        minx: record.get(2)?.parse().ok()?,
+        maxx: record.get(3)?.parse().ok()?,
+        miny: record.get(4)?.parse().ok()?,
+        maxy: record.get(5)?.parse().ok()?,
+    })
+}
+
+fn parse_point(record: &StringRecord) -> Option<Point> {
+    Some(Point {
+        x: record.get(2)?.parse().ok()?,
+        y: record.get(3)?.parse().ok()?,
+    })
+}
+
+fn parse_query(record: &StringRecord) -> Option<LoggedQuery> {
+    let at = Duration::from_micros(record.get(0)?.parse().ok()?);
+    let (query, fields) = match record.get(1)? {
+        "envelope" => (Query::Envelope(parse_bbox(record)?), 6),
+        "count" => (Query::Count(parse_bbox(record)?), 6),
+        "knn" => (
+            Query::Knn(parse_point(record)?, record.get(4)?.parse().ok()?),
+            5,
+        ),
+        "radius" => (
+            Query::Radius(parse_point(record)?, record.get(4)?.parse().ok()?),
+            5,
+        ),
+        _ => return None,
+    };
+    let expected = match record.get(fields) {
+        None | Some("") => None,
+        Some(e) => Some(e.parse().ok()?),
+    };
+    if record.len() > fields + 1 {
+        return None;
+    }
+    Some(LoggedQuery {
+        at,
+        query,
+        expected,
+    })
+}
+
+// panics on lines that aren't queries, like read does on broken datasets
+pub fn read_log(path: &str) -> Vec<LoggedQuery> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_path(path)
+        .unwrap();
+    reader
+        .records()
+        .map(|r| {
+            let r = r.unwrap();
+            parse_query(&r).unwrap_or_else(|| panic!("bad query {r:?} in {path}"))
+        })
+        .collect()
+}
+
+// the queries with the expected counts given next to them, so a replay can be recorded with its
+// own results
+pub fn write_log<'a, L>(path: &str, log: L)
+where
+    L: IntoIterator<Item = (&'a LoggedQuery, Option<usize>)>,
+{
+    let mut file = File::create(path).unwrap();
+    for (q, expected) in log {
+        let query = match &q.query {
+            Query::Envelope(e) => format!("envelope,{},{},{},{}", e.minx, e.maxx, e.miny, e.maxy),
+            Query::Count(e) => format!("count,{},{},{},{}", e.minx, e.maxx, e.miny, e.maxy),
+            Query::Knn(p, k) => format!("knn,{},{},{k}", p.x, p.y),
+            Query::Radius(p, m) => format!("radius,{},{},{m}", p.x, p.y),
+        };
+        let expected = expected.map_or(String::new(), |e| e.to_string());
+        file.write_all(format!("{},{query},{expected}\n", q.at.as_micros()).as_bytes())
+            .unwrap();
+    }
+}
+
+// an envelope file of genenvelopes as a log of envelope queries, all at time 0 so they replay
+// at max speed either way. the expected counts are the fifth column if the file has one, else
+// target, None if the target isn't a count.
+pub fn import_envelopes(path: &Path, target: Option<usize>) -> Option<Vec<LoggedQuery>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_path(path)
+        .ok()?;
+    let mut log = Vec::new();
+    for r in reader.records() {
+        let r = r.unwrap();
+        let field = |i: usize| r.get(i).unwrap().parse::<f32>().unwrap();
+        log.push(LoggedQuery {
+            at: Duration::ZERO,
+            query: Query::Envelope(BBox {
+                minx: field(0),
+                maxx: field(1),
+                miny: field(2),
+                maxy: field(3),
+            }),
+            expected: r.get(4).map_or(target, |c| c.parse().ok()),
+        });
+    }
+    Some(log)
+}
+
+// the query types a backend replays besides envelopes, which every SpatialIndex does. the
+// ones it has no trait for stay None and are skipped.
+pub trait Replay<T>: SpatialIndex<T> {
+    fn replay_count(&self, _env: &BBox) -> Option<usize> {
+        None
+    }
+    fn replay_knn(&self, _center: &Point, _k: usize) -> Option<usize> {
+        None
+    }
+    fn replay_radius(&self, _center: &Point, _radius_m: f64) -> Option<usize> {
+        None
+    }
+}
+
+fn knn_len<T, I: KnnQuery<T>>(index: &I, center: &Point, k: usize) -> Option<usize> {
+    let mut res = Vec::with_capacity(k);
+    index.query_knn(center, k, &mut res);
+    Some(res.len())
+}
+
+fn radius_len<T, I: RadiusQuery<T>>(index: &I, center: &Point, radius_m: f64) -> Option<usize> {
+    let mut res = Vec::new();
+    index.query_radius(center, radius_m, &mut res);
+    Some(res.len())
+}
+
+impl<T> Replay<T> for RTree<T>
+where
+    T: RTreeObject<Envelope = AABB<[f32; 2]>> + Clone + Located,
+{
+    fn replay_count(&self, env: &BBox) -> Option<usize> {
+        Some(self.query_count(env))
+    }
+    fn replay_knn(&self, center: &Point, k: usize) -> Option<usize> {
+        knn_len(self, center, k)
+    }
+    fn replay_radius(&self, center: &Point, radius_m: f64) -> Option<usize> {
+        radius_len(self, center, radius_m)
+    }
+}
+
+impl<T: Clone> Replay<T> for BallTree<T> {
+    fn replay_count(&self, env: &BBox) -> Option<usize> {
+        Some(self.query_count(env))
+    }
+    fn replay_knn(&self, center: &Point, k: usize) -> Option<usize> {
+        knn_len(self, center, k)
+    }
+    fn replay_radius(&self, center: &Point, radius_m: f64) -> Option<usize> {
+        radius_len(self, center, radius_m)
+    }
+}
+
+impl<T: Clone + Located> Replay<T> for HPRTree<T> {
+    fn replay_knn(&self, center: &Point, k: usize) -> Option<usize> {
+        knn_len(self, center, k)
+    }
+    fn replay_radius(&self, center: &Point, radius_m: f64) -> Option<usize> {
+        radius_len(self, center, radius_m)
+    }
+}
+
+impl<T: Clone, L: Layout> Replay<T> for PackedTree<T, L> {
+    fn replay_count(&self, env: &BBox) -> Option<usize> {
+        Some(self.query_count(env))
+    }
+}
+
+impl<T: Clone> Replay<T> for UniformGrid<T> {
+    fn replay_count(&self, env: &BBox) -> Option<usize> {
+        Some(self.query_count(env))
+    }
+}
+
+impl<T: Clone> Replay<T> for HexIndex<T> {
+    fn replay_count(&self, env: &BBox) -> Option<usize> {
+        Some(self.query_count(env))
+    }
+    fn replay_knn(&self, center: &Point, k: usize) -> Option<usize> {
+        knn_len(self, center, k)
+    }
+}
+
+impl<T: Clone> Replay<T> for S2Index<T> {
+    fn replay_count(&self, env: &BBox) -> Option<usize> {
+        Some(self.query_count(env))
+    }
+    fn replay_radius(&self, center: &Point, radius_m: f64) -> Option<usize> {
+        radius_len(self, center, radius_m)
+    }
+}
+
+// the number of results, None if the backend can't run this type of query
+pub fn run_query<T, I: Replay<T>>(index: &I, query: &Query) -> Option<usize> {
+    match query {
+        Query::Envelope(env) => {
+            let mut res = Vec::new();
+            index.query_with_list(env, &mut res);
+            Some(res.len())
+        }
+        Query::Count(env) => index.replay_count(env),
+        Query::Knn(center, k) => index.replay_knn(center, *k),
+        Query::Radius(center, m) => index.replay_radius(center, *m),
+    }
+}
+
+// runs the log against index until it ends or limit is up. returns the latency and result
+// count of every query that ran, None for the ones the backend doesn't support. at the
+// recorded pace the latency counts from when the query was due, so falling behind shows in it.
+pub fn replay<T, I: Replay<T>>(
+    index: &I,
+    log: &[LoggedQuery],
+    pace: Pace,
+    limit: Duration,
+) -> Vec<Option<(Duration, usize)>> {
+    let mut res = Vec::with_capacity(log.len());
+    let first = log.first().map_or(Duration::ZERO, |q| q.at);
+    let start = Instant::now();
+    for q in log {
+        let due = start + (q.at - first.min(q.at));
+        let begin = match pace {
+            Pace::Max => Instant::now(),
+            Pace::Recorded => {
+                let now = Instant::now();
+                if due > now {
+                    sleep(due - now);
+                }
+                due
+            }
+        };
+        let count = run_query(index, &q.query);
+        let end = Instant::now();
+        res.push(count.map(|c| (end - begin, c)));
+        if end - start > limit {
+            eprintln!("exceeded time limit after {} queries!", res.len());
+            break;
+        }
+    }
+    res
+}
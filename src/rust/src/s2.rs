@@ -0,0 +1,397 @@
+use hprtree::{BBox, Point};
+
+use crate::index::{
+    clone_bbox, contains, extent_of, Counter, NoCount, RadiusQuery, SpatialIndex, VisitQuery,
+};
+use crate::learned::hilbert;
+use crate::sphere::{angle, cap_bound, haversine_m, normalize, radius_to_angle, to_xyz};
+
+pub const MAX_LEVEL: u8 = 30;
+// cells a covering may use before refinement stops
+const DEFAULT_MAX_CELLS: usize = 16;
+
+// s2 style cell id: 3 face bits, 2 bits per level of hilbert position within the face, then a
+// marker bit whose position encodes the level. a cell covers exactly the leaf ids in
+// [range_min, range_max]. unlike s2 the hilbert curves of neighbouring faces are not joined up.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct CellId(pub u64);
+
+impl CellId {
+    pub fn from_face_ij(face: u8, level: u8, i: u32, j: u32) -> Self {
+        let shift = (MAX_LEVEL - level) as u32;
+        let pos = hilbert((i as u64) << shift, (j as u64) << shift, MAX_LEVEL as u32);
+        let leaf = ((face as u64) << 61) | (pos << 1) | 1;
+        CellId(leaf).parent(level)
+    }
+
+    pub fn from_point(p: &Point) -> Self {
+        let (face, u, v) = xyz_to_face_uv(&to_xyz(p));
+        let cells = 1u64 << MAX_LEVEL;
+        let i = ((uv_to_st(u) * cells as f64) as u64).min(cells - 1) as u32;
+        let j = ((uv_to_st(v) * cells as f64) as u64).min(cells - 1) as u32;
+        CellId::from_face_ij(face, MAX_LEVEL, i, j)
+    }
+
+    fn lsb(&self) -> u64 {
+        self.0 & self.0.wrapping_neg()
+    }
+
+    pub fn parent(&self, level: u8) -> Self {
+        let lsb = 1u64 << (2 * (MAX_LEVEL - level) as u32);
+        CellId((self.0 & lsb.wrapping_neg()) | lsb)
+    }
+
+    pub fn range_min(&self) -> u64 {
+        self.0 - (self.lsb() - 1)
+    }
+
+    pub fn range_max(&self) -> u64 {
+        self.0 + (self.lsb() - 1)
+    }
+}
+
+// s2's quadratic projection, keeps the cell areas within a factor of ~2 of each other
+fn uv_to_st(u: f64) -> f64 {
+    if u >= 0f64 {
+        0.5 * (1f64 + 3f64 * u).sqrt()
+    } else {
+        1f64 - 0.5 * (1f64 - 3f64 * u).sqrt()
+    }
+}
+
+fn st_to_uv(s: f64) -> f64 {
+    if s >= 0.5 {
+        (4f64 * s * s - 1f64) / 3f64
+    } else {
+        (1f64 - 4f64 * (1f64 - s) * (1f64 - s)) / 3f64
+    }
+}
+
+fn xyz_to_face_uv(p: &[f64; 3]) -> (u8, f64, f64) {
+    let (ax, ay, az) = (p[0].abs(), p[1].abs(), p[2].abs());
+    let face = if ax >= ay && ax >= az {
+        if p[0] >= 0f64 {
+            0
+        } else {
+            3
+        }
+    } else if ay >= az {
+        if p[1] >= 0f64 {
+            1
+        } else {
+            4
+        }
+    } else if p[2] >= 0f64 {
+        2
+    } else {
+        5
+    };
+    let (u, v) = match face {
+        0 => (p[1] / p[0], p[2] / p[0]),
+        1 => (-p[0] / p[1], p[2] / p[1]),
+        2 => (-p[0] / p[2], -p[1] / p[2]),
+        3 => (p[2] / p[0], p[1] / p[0]),
+        4 => (p[2] / p[1], -p[0] / p[1]),
+        _ => (-p[1] / p[2], -p[0] / p[2]),
+    };
+    (face, u, v)
+}
+
+fn face_uv_to_xyz(face: u8, u: f64, v: f64) -> [f64; 3] {
+    let p = match face {
+        0 => [1f64, u, v],
+        1 => [-u, 1f64, v],
+        2 => [-u, -v, 1f64],
+        3 => [-1f64, -v, -u],
+        4 => [v, -1f64, -u],
+        _ => [v, u, -1f64],
+    };
+    normalize(p)
+}
+
+// a cell while covering, kept as face/level/ij so children are cheap to produce
+#[derive(Clone, Copy)]
+struct Cell {
+    face: u8,
+    level: u8,
+    i: u32,
+    j: u32,
+}
+
+impl Cell {
+    fn faces() -> [Cell; 6] {
+        [0, 1, 2, 3, 4, 5].map(|face| Cell {
+            face,
+            level: 0,
+            i: 0,
+            j: 0,
+        })
+    }
+
+    fn children(&self) -> [Cell; 4] {
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(di, dj)| Cell {
+            face: self.face,
+            level: self.level + 1,
+            i: self.i * 2 + di,
+            j: self.j * 2 + dj,
+        })
+    }
+
+    fn vertex(&self, di: u32, dj: u32) -> [f64; 3] {
+        let size = 1f64 / (1u64 << self.level) as f64;
+        let s = (self.i + di) as f64 * size;
+        let t = (self.j + dj) as f64 * size;
+        face_uv_to_xyz(self.face, st_to_uv(s), st_to_uv(t))
+    }
+
+    // center and angular radius of a cap around the cell. cells are convex, so the farthest
+    // point from the center is one of the vertices.
+    fn cap(&self) -> ([f64; 3], f64) {
+        let size = 1f64 / (1u64 << self.level) as f64;
+        let s = (self.i as f64 + 0.5) * size;
+        let t = (self.j as f64 + 0.5) * size;
+        let center = face_uv_to_xyz(self.face, st_to_uv(s), st_to_uv(t));
+        let mut radius = 0f64;
+        for (di, dj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            radius = radius.max(angle(&center, &self.vertex(di, dj)));
+        }
+        // a little slack against rounding
+        (center, radius * (1f64 + 1e-9) + 1e-12)
+    }
+
+    fn id(&self) -> CellId {
+        CellId::from_face_ij(self.face, self.level, self.i, self.j)
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum Relation {
+    Disjoint,
+    Partial,
+    Inside,
+}
+
+trait Region {
+    fn relate(&self, cell: &Cell) -> Relation;
+}
+
+// plain lon/lat envelope in degrees
+struct RectRegion<'a>(&'a BBox);
+
+impl Region for RectRegion<'_> {
+    fn relate(&self, cell: &Cell) -> Relation {
+        let (center, radius) = cell.cap();
+        let bound = cap_bound(&center, radius);
+        if !bound.intersects(self.0) {
+            Relation::Disjoint
+        } else if bound.inside(self.0) {
+            Relation::Inside
+        } else {
+            Relation::Partial
+        }
+    }
+}
+
+// spherical cap, center on the unit sphere and radius in radians
+struct CapRegion {
+    center: [f64; 3],
+    radius: f64,
+}
+
+impl Region for CapRegion {
+    fn relate(&self, cell: &Cell) -> Relation {
+        let (center, radius) = cell.cap();
+        let d = angle(&self.center, &center);
+        if d > self.radius + radius {
+            Relation::Disjoint
+        } else if d + radius <= self.radius {
+            Relation::Inside
+        } else {
+            Relation::Partial
+        }
+    }
+}
+
+// approximates a region with cells, refining level by level as long as max_cells allows it
+pub struct RegionCoverer {
+    pub max_level: u8,
+    pub max_cells: usize,
+}
+
+impl Default for RegionCoverer {
+    fn default() -> Self {
+        RegionCoverer {
+            max_level: MAX_LEVEL,
+            max_cells: DEFAULT_MAX_CELLS,
+        }
+    }
+}
+
+impl RegionCoverer {
+    // relate calls count as bbox tests
+    pub fn cover_rect<C: Counter>(&self, env: &BBox, counter: &mut C) -> Vec<CellId> {
+        self.cover(&RectRegion(env), counter)
+    }
+
+    pub fn cover_cap(&self, center: &Point, radius_m: f64) -> Vec<CellId> {
+        self.cover(
+            &CapRegion {
+                center: to_xyz(center),
+                radius: radius_to_angle(radius_m),
+            },
+            &mut NoCount,
+        )
+    }
+
+    fn cover<R: Region, C: Counter>(&self, region: &R, counter: &mut C) -> Vec<CellId> {
+        let mut result = Vec::new();
+        let mut frontier = Vec::new();
+        for cell in Cell::faces() {
+            counter.bbox_tests(1);
+            match region.relate(&cell) {
+                Relation::Disjoint => (),
+                Relation::Inside => result.push(cell),
+                Relation::Partial => frontier.push(cell),
+            }
+        }
+        let mut level = 0;
+        while !frontier.is_empty() && level < self.max_level {
+            let mut inside = Vec::new();
+            let mut partial = Vec::new();
+            for cell in &frontier {
+                for child in cell.children() {
+                    counter.bbox_tests(1);
+                    match region.relate(&child) {
+                        Relation::Disjoint => (),
+                        Relation::Inside => inside.push(child),
+                        Relation::Partial => partial.push(child),
+                    }
+                }
+            }
+            if result.len() + inside.len() + partial.len() > self.max_cells {
+                break;
+            }
+            result.extend(inside);
+            frontier = partial;
+            level += 1;
+        }
+        result.extend(frontier);
+        let mut ids: Vec<CellId> = result.iter().map(|c| c.id()).collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+// points stored in leaf cell id order, queries scan the id ranges of a covering
+pub struct S2Index<T> {
+    ids: Vec<u64>,
+    lon: Vec<f32>,
+    lat: Vec<f32>,
+    elements: Vec<T>,
+    extent: BBox,
+    coverer: RegionCoverer,
+}
+
+impl<T> S2Index<T> {
+    fn scan_covering<F>(&self, covering: &[CellId], mut f: F)
+    where
+        F: FnMut(usize, &Point),
+    {
+        for cell in covering {
+            let (lo, hi) = (cell.range_min(), cell.range_max());
+            let mut i = self.ids.partition_point(|&id| id < lo);
+            while i < self.ids.len() && self.ids[i] <= hi {
+                f(
+                    i,
+                    &Point {
+                        x: self.lon[i],
+                        y: self.lat[i],
+                    },
+                );
+                i += 1;
+            }
+        }
+    }
+}
+
+impl<T> SpatialIndex<T> for S2Index<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "s2";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let mut keyed: Vec<(u64, T, Point)> = data
+            .into_iter()
+            .map(|(e, p)| (CellId::from_point(&p).0, e, p))
+            .collect();
+        keyed.sort_by_key(|e| e.0);
+
+        let mut ids = Vec::with_capacity(keyed.len());
+        let mut lon = Vec::with_capacity(keyed.len());
+        let mut lat = Vec::with_capacity(keyed.len());
+        let mut elements = Vec::with_capacity(keyed.len());
+        for (id, e, p) in keyed {
+            ids.push(id);
+            lon.push(p.x);
+            lat.push(p.y);
+            elements.push(e);
+        }
+        S2Index {
+            ids,
+            lon,
+            lat,
+            elements,
+            extent,
+            coverer: RegionCoverer::default(),
+        }
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.ids.capacity() * std::mem::size_of::<u64>()
+            + (self.lon.capacity() + self.lat.capacity()) * std::mem::size_of::<f32>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+    }
+}
+
+impl<T> VisitQuery<T> for S2Index<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        let covering = self.coverer.cover_rect(env, counter);
+        counter.nodes(covering.len());
+        self.scan_covering(&covering, |i, p| {
+            counter.entries(1);
+            if contains(env, p) {
+                visit(&self.elements[i]);
+            }
+        });
+    }
+}
+
+impl<T> RadiusQuery<T> for S2Index<T>
+where
+    T: Clone,
+{
+    fn query_radius(&self, center: &Point, radius_m: f64, list: &mut Vec<T>) {
+        let covering = self.coverer.cover_cap(center, radius_m);
+        self.scan_covering(&covering, |i, p| {
+            if haversine_m(center, p) <= radius_m {
+                list.push(self.elements[i].clone());
+            }
+        });
+    }
+}
@@ -0,0 +1,222 @@
+use hprtree::{BBox, Point};
+
+use crate::index::{clone_bbox, extent_of, Counter, SpatialIndex, VisitQuery};
+
+// calls hit(i) for every i with (lon[i], lat[i]) inside env, using the widest vector unit available
+pub fn scan_box<F>(lon: &[f32], lat: &[f32], env: &BBox, mut hit: F)
+where
+    F: FnMut(usize),
+{
+    assert!(lon.len() == lat.len());
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            unsafe { scan_box_avx2(lon, lat, env, &mut hit) };
+        } else {
+            // sse is part of the x86_64 baseline
+            unsafe { scan_box_sse(lon, lat, env, &mut hit) };
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    scan_box_scalar(lon, lat, env, 0, &mut hit);
+}
+
+fn scan_box_scalar<F>(lon: &[f32], lat: &[f32], env: &BBox, from: usize, hit: &mut F)
+where
+    F: FnMut(usize),
+{
+    for i in from..lon.len() {
+        let (x, y) = (lon[i], lat[i]);
+        if x >= env.minx && x <= env.maxx && y >= env.miny && y <= env.maxy {
+            hit(i);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn scan_box_avx2<F>(lon: &[f32], lat: &[f32], env: &BBox, hit: &mut F)
+where
+    F: FnMut(usize),
+{
+    use std::arch::x86_64::*;
+
+    let minx = _mm256_set1_ps(env.minx);
+    let maxx = _mm256_set1_ps(env.maxx);
+    let miny = _mm256_set1_ps(env.miny);
+    let maxy = _mm256_set1_ps(env.maxy);
+    let chunks = lon.len() / 8;
+    for c in 0..chunks {
+        let x = _mm256_loadu_ps(lon.as_ptr().add(c * 8));
+        let y = _mm256_loadu_ps(lat.as_ptr().add(c * 8));
+        let inx = _mm256_and_ps(
+            _mm256_cmp_ps::<_CMP_GE_OQ>(x, minx),
+            _mm256_cmp_ps::<_CMP_LE_OQ>(x, maxx),
+        );
+        let iny = _mm256_and_ps(
+            _mm256_cmp_ps::<_CMP_GE_OQ>(y, miny),
+            _mm256_cmp_ps::<_CMP_LE_OQ>(y, maxy),
+        );
+        let mut mask = _mm256_movemask_ps(_mm256_and_ps(inx, iny)) as u32;
+        while mask != 0 {
+            hit(c * 8 + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+    }
+    scan_box_scalar(lon, lat, env, chunks * 8, hit);
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn scan_box_sse<F>(lon: &[f32], lat: &[f32], env: &BBox, hit: &mut F)
+where
+    F: FnMut(usize),
+{
+    use std::arch::x86_64::*;
+
+    let minx = _mm_set1_ps(env.minx);
+    let maxx = _mm_set1_ps(env.maxx);
+    let miny = _mm_set1_ps(env.miny);
+    let maxy = _mm_set1_ps(env.maxy);
+    let chunks = lon.len() / 4;
+    for c in 0..chunks {
+        let x = _mm_loadu_ps(lon.as_ptr().add(c * 4));
+        let y = _mm_loadu_ps(lat.as_ptr().add(c * 4));
+        let inx = _mm_and_ps(_mm_cmpge_ps(x, minx), _mm_cmple_ps(x, maxx));
+        let iny = _mm_and_ps(_mm_cmpge_ps(y, miny), _mm_cmple_ps(y, maxy));
+        let mut mask = _mm_movemask_ps(_mm_and_ps(inx, iny)) as u32;
+        while mask != 0 {
+            hit(c * 4 + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+    }
+    scan_box_scalar(lon, lat, env, chunks * 4, hit);
+}
+
+// brute force over flat coordinate arrays, the elements live in a third array
+pub struct LinearScan<T> {
+    lon: Vec<f32>,
+    lat: Vec<f32>,
+    elements: Vec<T>,
+    extent: BBox,
+}
+
+impl<T> SpatialIndex<T> for LinearScan<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "linscan";
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let mut lon = Vec::with_capacity(data.len());
+        let mut lat = Vec::with_capacity(data.len());
+        let mut elements = Vec::with_capacity(data.len());
+        for (e, p) in data {
+            lon.push(p.x);
+            lat.push(p.y);
+            elements.push(e);
+        }
+        LinearScan {
+            lon,
+            lat,
+            elements,
+            extent,
+        }
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + (self.lon.capacity() + self.lat.capacity()) * std::mem::size_of::<f32>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+    }
+}
+
+impl<T> VisitQuery<T> for LinearScan<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        // one array, every point tested
+        counter.nodes(1);
+        counter.entries(self.lon.len());
+        scan_box(&self.lon, &self.lat, env, |i| visit(&self.elements[i]));
+    }
+}
+
+// everything sorted by x, queries binary search the x range and only filter that slice
+pub struct SortedArray<T> {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    elements: Vec<T>,
+    extent: BBox,
+}
+
+impl<T> SpatialIndex<T> for SortedArray<T>
+where
+    T: Clone,
+{
+    const NAME: &'static str = "sortedx";
+
+    fn build(mut data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        data.sort_by(|a, b| a.1.x.total_cmp(&b.1.x));
+        let mut xs = Vec::with_capacity(data.len());
+        let mut ys = Vec::with_capacity(data.len());
+        let mut elements = Vec::with_capacity(data.len());
+        for (e, p) in data {
+            xs.push(p.x);
+            ys.push(p.y);
+            elements.push(e);
+        }
+        SortedArray {
+            xs,
+            ys,
+            elements,
+            extent,
+        }
+    }
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + (self.xs.capacity() + self.ys.capacity()) * std::mem::size_of::<f32>()
+            + self.elements.capacity() * std::mem::size_of::<T>()
+    }
+}
+
+impl<T> VisitQuery<T> for SortedArray<T> {
+    fn visit_with_counter<'a, C, F>(&'a self, env: &BBox, counter: &mut C, mut visit: F)
+    where
+        C: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        let from = self.xs.partition_point(|&x| x < env.minx);
+        let to = self.xs.partition_point(|&x| x <= env.maxx);
+        counter.nodes(1);
+        if from >= to {
+            return;
+        }
+        counter.entries(to - from);
+        scan_box(&self.xs[from..to], &self.ys[from..to], env, |i| {
+            visit(&self.elements[from + i])
+        });
+    }
+}
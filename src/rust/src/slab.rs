@@ -0,0 +1,246 @@
+use std::marker::PhantomData;
+
+use hprtree::{BBox, Point};
+
+use crate::index::{clone_bbox, contains, extent_of, Counter, SpatialIndex, VisitQuery};
+use crate::packed::{hilbert_order, NODE_CAPACITY};
+use crate::quality::TreeQuality;
+
+// how the coordinate arrays the leaf scans read store a coordinate
+pub trait Coords {
+    const INDEX_NAME: &'static str;
+    type Word: Copy + Ord;
+    // whether equal words mean equal coordinates. if not, points whose words equal a border of
+    // the query are refined with the exact coordinates.
+    const EXACT: bool;
+    // monotonic in v, values outside [min, max] are clamped
+    fn encode(v: f32, min: f32, max: f32) -> Self::Word;
+}
+
+pub struct F32;
+pub struct Q32;
+pub struct Q16;
+
+impl Coords for F32 {
+    const INDEX_NAME: &'static str = "slab_f32";
+    type Word = u32;
+    const EXACT: bool = true;
+    fn encode(v: f32, _: f32, _: f32) -> u32 {
+        ordered_bits(v)
+    }
+}
+
+// the float bits, flipped so that the integer order is the float order
+pub fn ordered_bits(v: f32) -> u32 {
+    // -0 and 0 have to end up as the same word
+    let bits = if v == 0f32 { 0u32 } else { v.to_bits() };
+    if bits >> 31 == 1 {
+        !bits
+    } else {
+        bits | (1 << 31)
+    }
+}
+
+fn fixed_point(v: f32, min: f32, max: f32, bits: u32) -> u64 {
+    if max <= min {
+        return 0;
+    }
+    let cells = ((1u64 << bits) - 1) as f64;
+    ((v as f64 - min as f64) / (max as f64 - min as f64) * cells).clamp(0f64, cells) as u64
+}
+
+impl Coords for Q32 {
+    const INDEX_NAME: &'static str = "slab_q32";
+    type Word = u32;
+    const EXACT: bool = false;
+    fn encode(v: f32, min: f32, max: f32) -> u32 {
+        fixed_point(v, min, max, 32) as u32
+    }
+}
+
+impl Coords for Q16 {
+    const INDEX_NAME: &'static str = "slab_q16";
+    type Word = u16;
+    const EXACT: bool = false;
+    fn encode(v: f32, min: f32, max: f32) -> u16 {
+        fixed_point(v, min, max, 16) as u16
+    }
+}
+
+// packed tree whose leaves are runs of the coordinate arrays, the elements live in a slab
+// indexed the same way and are only touched for hits
+pub struct SlabTree<T, C: Coords> {
+    // node bounds level by level, leaves first. node i of a level has the nodes
+    // [i * NODE_CAPACITY, (i + 1) * NODE_CAPACITY) of the level below as children, leaf i the
+    // points in that range.
+    levels: Vec<Vec<[f32; 4]>>,
+    xs: Vec<C::Word>,
+    ys: Vec<C::Word>,
+    // full precision coordinates for refinement, empty if the words are exact
+    exact: Vec<Point>,
+    slab: Vec<T>,
+    extent: BBox,
+    coords: PhantomData<C>,
+}
+
+pub fn bounds(boxes: &[[f32; 4]]) -> [f32; 4] {
+    let mut b = [
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+    ];
+    for e in boxes {
+        b[0] = b[0].min(e[0]);
+        b[1] = b[1].max(e[1]);
+        b[2] = b[2].min(e[2]);
+        b[3] = b[3].max(e[3]);
+    }
+    b
+}
+
+// metrics of the implicit level layout the slab and compressed trees share
+pub fn levels_quality(levels: &[Vec<[f32; 4]>], len: usize) -> TreeQuality {
+    let mut quality = TreeQuality::default();
+    for (k, level) in levels.iter().enumerate() {
+        let depth = levels.len() - 1 - k;
+        for (n, b) in level.iter().enumerate() {
+            let from = n * NODE_CAPACITY;
+            if k == 0 {
+                let to = (from + NODE_CAPACITY).min(len);
+                quality.add_leaf(depth, b, to - from, NODE_CAPACITY);
+            } else {
+                let to = (from + NODE_CAPACITY).min(levels[k - 1].len());
+                quality.add_node(depth, b, &levels[k - 1][from..to], NODE_CAPACITY);
+            }
+        }
+    }
+    quality
+}
+
+impl<T, C> SpatialIndex<T> for SlabTree<T, C>
+where
+    T: Clone,
+    C: Coords,
+{
+    const NAME: &'static str = C::INDEX_NAME;
+
+    fn build(data: Vec<(T, Point)>) -> Self {
+        let extent = extent_of(data.iter().map(|e| &e.1));
+        let (points, slab) = hilbert_order(data, &extent);
+
+        let mut levels = Vec::new();
+        let mut level: Vec<[f32; 4]> = points
+            .chunks(NODE_CAPACITY)
+            .map(|chunk| {
+                let boxes: Vec<[f32; 4]> = chunk.iter().map(|p| [p.x, p.x, p.y, p.y]).collect();
+                bounds(&boxes)
+            })
+            .collect();
+        while level.len() > 1 {
+            let next = level.chunks(NODE_CAPACITY).map(bounds).collect();
+            levels.push(level);
+            level = next;
+        }
+        if !level.is_empty() {
+            levels.push(level);
+        }
+
+        let xs = points
+            .iter()
+            .map(|p| C::encode(p.x, extent.minx, extent.maxx))
+            .collect();
+        let ys = points
+            .iter()
+            .map(|p| C::encode(p.y, extent.miny, extent.maxy))
+            .collect();
+        SlabTree {
+            levels,
+            xs,
+            ys,
+            exact: if C::EXACT { Vec::new() } else { points },
+            slab,
+            extent,
+            coords: PhantomData,
+        }
+    }
+    fn len(&self) -> usize {
+        self.slab.len()
+    }
+    fn extent(&self) -> BBox {
+        clone_bbox(&self.extent)
+    }
+    fn query_with_list(&self, env: &BBox, list: &mut Vec<T>) {
+        self.query_visit(env, |e| list.push(e.clone()))
+    }
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.levels.capacity() * std::mem::size_of::<Vec<[f32; 4]>>()
+            + self
+                .levels
+                .iter()
+                .map(|l| l.capacity() * std::mem::size_of::<[f32; 4]>())
+                .sum::<usize>()
+            + (self.xs.capacity() + self.ys.capacity()) * std::mem::size_of::<C::Word>()
+            + self.exact.capacity() * std::mem::size_of::<Point>()
+            + self.slab.capacity() * std::mem::size_of::<T>()
+    }
+    fn quality(&self) -> Option<TreeQuality> {
+        Some(levels_quality(&self.levels, self.slab.len()))
+    }
+}
+
+impl<T, C> VisitQuery<T> for SlabTree<T, C>
+where
+    C: Coords,
+{
+    fn visit_with_counter<'a, K, F>(&'a self, env: &BBox, counter: &mut K, mut visit: F)
+    where
+        K: Counter,
+        F: FnMut(&'a T),
+        T: 'a,
+    {
+        if self.levels.is_empty() {
+            return;
+        }
+        let e = &self.extent;
+        let (x0, x1) = (
+            C::encode(env.minx, e.minx, e.maxx),
+            C::encode(env.maxx, e.minx, e.maxx),
+        );
+        let (y0, y1) = (
+            C::encode(env.miny, e.miny, e.maxy),
+            C::encode(env.maxy, e.miny, e.maxy),
+        );
+        let mut stack = vec![(self.levels.len() - 1, 0)];
+        while let Some((level, n)) = stack.pop() {
+            let b = &self.levels[level][n];
+            counter.nodes(1);
+            counter.bbox_tests(1);
+            if b[0] > env.maxx || b[1] < env.minx || b[2] > env.maxy || b[3] < env.miny {
+                continue;
+            }
+            let from = n * NODE_CAPACITY;
+            if level > 0 {
+                let to = (from + NODE_CAPACITY).min(self.levels[level - 1].len());
+                stack.extend((from..to).map(|c| (level - 1, c)));
+                continue;
+            }
+            let to = (from + NODE_CAPACITY).min(self.slab.len());
+            counter.entries(to - from);
+            for i in from..to {
+                let (x, y) = (self.xs[i], self.ys[i]);
+                if x < x0 || x > x1 || y < y0 || y > y1 {
+                    continue;
+                }
+                // strictly between the border words means strictly inside env
+                if C::EXACT
+                    || (x > x0 && x < x1 && y > y0 && y < y1)
+                    || contains(env, &self.exact[i])
+                {
+                    visit(&self.slab[i]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use hprtree::{BBox, Point};
+
+// mean earth radius
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+// points are lon (x) / lat (y) in degrees, everything else works on the unit sphere
+pub fn to_xyz(p: &Point) -> [f64; 3] {
+    let lat = (p.y as f64).to_radians();
+    let lon = (p.x as f64).to_radians();
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+pub fn to_lonlat(v: &[f64; 3]) -> (f64, f64) {
+    let lat = v[2].atan2((v[0] * v[0] + v[1] * v[1]).sqrt());
+    let lon = v[1].atan2(v[0]);
+    (lon.to_degrees(), lat.to_degrees())
+}
+
+pub fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    [v[0] / len, v[1] / len, v[2] / len]
+}
+
+pub fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// angle between two unit vectors in radians, atan2 form so it stays accurate for tiny angles
+pub fn angle(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    let cross = [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ];
+    dot(&cross, &cross).sqrt().atan2(dot(a, b))
+}
+
+// great circle distance in meters
+pub fn haversine_m(a: &Point, b: &Point) -> f64 {
+    let (lat1, lat2) = ((a.y as f64).to_radians(), (b.y as f64).to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.x as f64 - a.x as f64).to_radians();
+    let h = (dlat / 2f64).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2f64).sin().powi(2);
+    2f64 * EARTH_RADIUS_M * h.sqrt().min(1f64).asin()
+}
+
+// lat/lon rectangle in degrees, lon_lo > lon_hi means it crosses the antimeridian
+pub struct LonLatRect {
+    pub lon_lo: f64,
+    pub lon_hi: f64,
+    pub lat_lo: f64,
+    pub lat_hi: f64,
+}
+
+impl LonLatRect {
+    pub fn intersects(&self, env: &BBox) -> bool {
+        if self.lat_hi < env.miny as f64 || self.lat_lo > env.maxy as f64 {
+            return false;
+        }
+        if self.lon_lo <= self.lon_hi {
+            self.lon_lo <= env.maxx as f64 && self.lon_hi >= env.minx as f64
+        } else {
+            env.maxx as f64 >= self.lon_lo || env.minx as f64 <= self.lon_hi
+        }
+    }
+
+    pub fn inside(&self, env: &BBox) -> bool {
+        if self.lat_lo < env.miny as f64 || self.lat_hi > env.maxy as f64 {
+            return false;
+        }
+        if self.lon_lo <= self.lon_hi {
+            self.lon_lo >= env.minx as f64 && self.lon_hi <= env.maxx as f64
+        } else {
+            env.minx <= -180f32 && env.maxx >= 180f32
+        }
+    }
+
+    // one or two (if crossing the antimeridian) plain boxes covering the rect, rounded outwards to f32
+    pub fn to_bboxes(&self) -> Vec<BBox> {
+        let (miny, maxy) = (
+            (self.lat_lo as f32).next_down(),
+            (self.lat_hi as f32).next_up(),
+        );
+        let (minx, maxx) = (
+            (self.lon_lo as f32).next_down(),
+            (self.lon_hi as f32).next_up(),
+        );
+        if self.lon_lo <= self.lon_hi {
+            vec![BBox {
+                minx,
+                maxx,
+                miny,
+                maxy,
+            }]
+        } else {
+            vec![
+                BBox {
+                    minx,
+                    maxx: 180f32,
+                    miny,
+                    maxy,
+                },
+                BBox {
+                    minx: -180f32,
+                    maxx,
+                    miny,
+                    maxy,
+                },
+            ]
+        }
+    }
+}
+
+// bounding lat/lon rect of the spherical cap around center with the given angle (radians)
+pub fn cap_bound(center: &[f64; 3], radius: f64) -> LonLatRect {
+    let (lon, lat) = to_lonlat(center);
+    let r = radius.to_degrees();
+    let (lat_lo, lat_hi) = (lat - r, lat + r);
+    if lat_lo <= -90f64 || lat_hi >= 90f64 {
+        // contains a pole, so every longitude
+        return LonLatRect {
+            lon_lo: -180f64,
+            lon_hi: 180f64,
+            lat_lo: lat_lo.max(-90f64),
+            lat_hi: lat_hi.min(90f64),
+        };
+    }
+    let dlon = (radius.sin() / lat.to_radians().cos())
+        .min(1f64)
+        .asin()
+        .to_degrees();
+    let (mut lon_lo, mut lon_hi) = (lon - dlon, lon + dlon);
+    if dlon >= 180f64 {
+        lon_lo = -180f64;
+        lon_hi = 180f64;
+    } else {
+        if lon_lo < -180f64 {
+            lon_lo += 360f64;
+        }
+        if lon_hi > 180f64 {
+            lon_hi -= 360f64;
+        }
+    }
+    LonLatRect {
+        lon_lo,
+        lon_hi,
+        lat_lo,
+        lat_hi,
+    }
+}
+
+pub fn radius_to_angle(radius_m: f64) -> f64 {
+    radius_m / EARTH_RADIUS_M
+}
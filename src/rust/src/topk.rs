@@ -0,0 +1,131 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::marker::PhantomData;
+
+use hprtree::{BBox, Point};
+
+use crate::index::{contains, VisitQuery};
+use crate::packed::{LevelTree, NodeSummary};
+use crate::slab::bounds;
+
+// the numeric payload field a top-k query ranks by, elements without a value are never returned
+pub trait RankKey<T> {
+    // used for the result/ file names
+    const NAME: &'static str;
+
+    fn rank(e: &T) -> Option<i64>;
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    bbox: [f32; 4],
+    // None if no element below has a rank
+    max_rank: Option<i64>,
+}
+
+impl NodeSummary for Node {
+    fn bbox(&self) -> &[f32; 4] {
+        &self.bbox
+    }
+    fn combine(children: &[Node]) -> Node {
+        let boxes: Vec<[f32; 4]> = children.iter().map(|n| n.bbox).collect();
+        Node {
+            bbox: bounds(&boxes),
+            max_rank: children.iter().map(|n| n.max_rank).max().unwrap(),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Entry {
+    // level and position in it
+    Node(usize, usize),
+    Element(usize),
+}
+
+// packed hilbert tree with the max rank of every subtree. the best-first traversal pops
+// elements in rank order, so it stops after k of them and never looks at subtrees whose max
+// is below the k-th rank.
+pub struct TopKTree<T, K> {
+    tree: LevelTree<Node, T>,
+    _key: PhantomData<K>,
+}
+
+impl<T, K> TopKTree<T, K>
+where
+    T: Clone,
+    K: RankKey<T>,
+{
+    pub fn build(data: Vec<(T, Point)>) -> Self {
+        TopKTree {
+            tree: LevelTree::build(data, |p, e| Node {
+                bbox: [p.x, p.x, p.y, p.y],
+                max_rank: K::rank(e),
+            }),
+            _key: PhantomData,
+        }
+    }
+
+    // the k highest ranked elements inside env, highest first
+    pub fn query_top_k(&self, env: &BBox, k: usize, list: &mut Vec<T>) {
+        let tree = &self.tree;
+        let mut heap = BinaryHeap::new();
+        if let Some((level, n)) = tree.root().filter(|_| k > 0) {
+            if let Some(rank) = tree.levels[level][n].max_rank {
+                heap.push((rank, Entry::Node(level, n)));
+            }
+        }
+        let mut found = 0;
+        while let Some((_, entry)) = heap.pop() {
+            let (level, n) = match entry {
+                Entry::Element(i) => {
+                    list.push(tree.items[i].clone());
+                    found += 1;
+                    if found == k {
+                        return;
+                    }
+                    continue;
+                }
+                Entry::Node(level, n) => (level, n),
+            };
+            let b = tree.levels[level][n].bbox();
+            if b[0] > env.maxx || b[1] < env.minx || b[2] > env.maxy || b[3] < env.miny {
+                continue;
+            }
+            let children = tree.children(level, n);
+            if level > 0 {
+                for c in children {
+                    if let Some(rank) = tree.levels[level - 1][c].max_rank {
+                        heap.push((rank, Entry::Node(level - 1, c)));
+                    }
+                }
+                continue;
+            }
+            for i in children {
+                if !contains(env, &tree.points[i]) {
+                    continue;
+                }
+                if let Some(rank) = K::rank(&tree.items[i]) {
+                    heap.push((rank, Entry::Element(i)));
+                }
+            }
+        }
+    }
+}
+
+// the baseline: every hit of the envelope query, sorted by rank and cut off after k
+pub fn naive_top_k<T, K, I>(index: &I, env: &BBox, k: usize, list: &mut Vec<T>)
+where
+    T: Clone,
+    K: RankKey<T>,
+    I: VisitQuery<T>,
+{
+    let mut ranked = Vec::new();
+    index.query_visit(env, |e| {
+        if let Some(rank) = K::rank(e) {
+            ranked.push((rank, e));
+        }
+    });
+    ranked.sort_by_key(|r| Reverse(r.0));
+    list.extend(ranked.into_iter().take(k).map(|(_, e)| e.clone()));
+}
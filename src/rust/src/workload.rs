@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use hprtree::{BBox, Point};
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::filtered::Attributes;
+
+// a place queries concentrate on, centres fall uniformly within radius degrees of it
+#[derive(Clone, Debug)]
+pub struct Hotspot {
+    pub lon: f32,
+    pub lat: f32,
+    pub radius: f32,
+    pub weight: f64,
+}
+
+impl Hotspot {
+    // "lon:lat:radius:weight"
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split(':').map(|p| p.parse::<f64>().ok());
+        let hotspot = Hotspot {
+            lon: parts.next()?? as f32,
+            lat: parts.next()?? as f32,
+            radius: parts.next()?? as f32,
+            weight: parts.next()??,
+        };
+        (parts.next().is_none() && hotspot.radius >= 0f32 && hotspot.weight > 0f64)
+            .then_some(hotspot)
+    }
+}
+
+// where the centres of a query stream come from
+#[derive(Clone, Debug)]
+pub enum Centres {
+    // dataset points by a zipf distribution with this exponent over a shuffled ranking, so a
+    // few points get most of the queries
+    Zipf(f64),
+    // dataset points weighted by their population
+    Population,
+    Hotspots(Vec<Hotspot>),
+}
+
+impl Centres {
+    // used for the result/ file names
+    pub fn name(&self) -> String {
+        match self {
+            Centres::Zipf(s) => format!("zipf{s}"),
+            Centres::Population => "population".to_string(),
+            Centres::Hotspots(h) => format!("hotspots{}", h.len()),
+        }
+    }
+
+    // "zipf:<s>", "population" or "hotspots:<lon>:<lat>:<radius>:<weight>,..."
+    pub fn parse(s: &str) -> Option<Self> {
+        if s == "population" {
+            return Some(Centres::Population);
+        }
+        if let Some(exponent) = s.strip_prefix("zipf:") {
+            let exponent = exponent.parse::<f64>().ok()?;
+            return (exponent >= 0f64).then_some(Centres::Zipf(exponent));
+        }
+        let hotspots = s
+            .strip_prefix("hotspots:")?
+            .split(',')
+            .map(Hotspot::parse)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Centres::Hotspots(hotspots))
+    }
+}
+
+// draws a query centre
+type Draw<'a> = Box<dyn Fn(&mut ChaCha8Rng) -> (f32, f32) + 'a>;
+
+// len query envelopes: one of the templates picked at random, moved to a centre drawn from
+// centres. None if the dataset can't give such centres, like population without any.
+pub fn generate<T: Attributes>(
+    centres: &Centres,
+    data: &[(T, Point)],
+    templates: &[BBox],
+    len: usize,
+    seed: u64,
+) -> Option<Vec<BBox>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let draw: Draw = match centres {
+        Centres::Zipf(s) => {
+            let mut ranking: Vec<usize> = (0..data.len()).collect();
+            ranking.shuffle(&mut rng);
+            let sampler = WeightedIndex::new((1..=data.len()).map(|r| (r as f64).powf(-s))).ok()?;
+            Box::new(move |rng| {
+                let p = &data[ranking[sampler.sample(rng)]].1;
+                (p.x, p.y)
+            })
+        }
+        Centres::Population => {
+            let sampler = WeightedIndex::new(data.iter().map(|e| e.0.population() as f64)).ok()?;
+            Box::new(move |rng| {
+                let p = &data[sampler.sample(rng)].1;
+                (p.x, p.y)
+            })
+        }
+        Centres::Hotspots(hotspots) => {
+            let sampler = WeightedIndex::new(hotspots.iter().map(|h| h.weight)).ok()?;
+            Box::new(move |rng| {
+                let h = &hotspots[sampler.sample(rng)];
+                // uniform in the disk
+                let r = h.radius * rng.gen::<f32>().sqrt();
+                let a = rng.gen_range(0f32..std::f32::consts::TAU);
+                (h.lon + r * a.cos(), h.lat + r * a.sin())
+            })
+        }
+    };
+    if templates.is_empty() {
+        return None;
+    }
+    let mut queries = Vec::with_capacity(len);
+    for _ in 0..len {
+        let t = &templates[rng.gen_range(0..templates.len())];
+        let (x, y) = draw(&mut rng);
+        let (w, h) = ((t.maxx - t.minx) / 2f32, (t.maxy - t.miny) / 2f32);
+        queries.push(BBox {
+            minx: x - w,
+            maxx: x + w,
+            miny: y - h,
+            maxy: y + h,
+        });
+    }
+    Some(queries)
+}
+
+// the latency below which a fraction p of the sorted timings fall
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
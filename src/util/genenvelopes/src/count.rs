@@ -0,0 +1,127 @@
+use hprtree::{BBox, Point};
+
+const NODE_CAPACITY: usize = 16;
+
+struct Node {
+    // minx maxx miny maxy
+    bbox: [f32; 4],
+    count: usize,
+}
+
+fn combine(children: &[Node]) -> Node {
+    let mut bbox = [
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+    ];
+    for c in children {
+        bbox[0] = bbox[0].min(c.bbox[0]);
+        bbox[1] = bbox[1].max(c.bbox[1]);
+        bbox[2] = bbox[2].min(c.bbox[2]);
+        bbox[3] = bbox[3].max(c.bbox[3]);
+    }
+    Node {
+        bbox,
+        count: children.iter().map(|c| c.count).sum(),
+    }
+}
+
+// static str packed tree that only counts. nodes inside the envelope add their count, only the
+// leaves on its border look at points, so nothing is materialized.
+pub struct CountTree {
+    // leaves first, node i of a level has the nodes [i * NODE_CAPACITY, (i + 1) * NODE_CAPACITY)
+    // of the level below as children, leaf i the points in that range
+    levels: Vec<Vec<Node>>,
+    points: Vec<Point>,
+}
+
+impl CountTree {
+    pub fn new(mut points: Vec<Point>) -> Self {
+        // sort tile recursive: vertical slices of whole leaves, sorted by y within
+        points.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let leaves = points.len().div_ceil(NODE_CAPACITY);
+        let slices = (leaves as f64).sqrt().ceil() as usize;
+        let slice_len = leaves.div_ceil(slices.max(1)).max(1) * NODE_CAPACITY;
+        for slice in points.chunks_mut(slice_len) {
+            slice.sort_by(|a, b| a.y.total_cmp(&b.y));
+        }
+
+        let mut level: Vec<Node> = points
+            .chunks(NODE_CAPACITY)
+            .map(|ps| {
+                combine(
+                    &ps.iter()
+                        .map(|p| Node {
+                            bbox: [p.x, p.x, p.y, p.y],
+                            count: 1,
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let mut levels = Vec::new();
+        while level.len() > 1 {
+            let next = level.chunks(NODE_CAPACITY).map(combine).collect();
+            levels.push(level);
+            level = next;
+        }
+        if !level.is_empty() {
+            levels.push(level);
+        }
+        CountTree { levels, points }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn extent(&self) -> BBox {
+        let b = self.levels.last().map_or([0f32; 4], |l| l[0].bbox);
+        BBox {
+            minx: b[0],
+            maxx: b[1],
+            miny: b[2],
+            maxy: b[3],
+        }
+    }
+
+    // in str order, which is as good as any for drawing random points
+    pub fn point(&self, i: usize) -> &Point {
+        &self.points[i]
+    }
+
+    // points inside env, borders included like the index queries
+    pub fn count(&self, env: &BBox) -> usize {
+        if self.levels.is_empty() {
+            return 0;
+        }
+        let mut res = 0;
+        let mut stack = vec![(self.levels.len() - 1, 0)];
+        while let Some((level, n)) = stack.pop() {
+            let node = &self.levels[level][n];
+            let b = &node.bbox;
+            if b[0] > env.maxx || b[1] < env.minx || b[2] > env.maxy || b[3] < env.miny {
+                continue;
+            }
+            if b[0] >= env.minx && b[1] <= env.maxx && b[2] >= env.miny && b[3] <= env.maxy {
+                res += node.count;
+                continue;
+            }
+            let from = n * NODE_CAPACITY;
+            if level > 0 {
+                let to = (from + NODE_CAPACITY).min(self.levels[level - 1].len());
+                stack.extend((from..to).map(|c| (level - 1, c)));
+                continue;
+            }
+            let to = (from + NODE_CAPACITY).min(self.points.len());
+            res += self.points[from..to]
+                .iter()
+                .filter(|p| {
+                    p.x >= env.minx && p.x <= env.maxx && p.y >= env.miny && p.y <= env.maxy
+                })
+                .count();
+        }
+        res
+    }
+}
@@ -1,6 +1,5 @@
-#![feature(float_next_up_down)]
-
 use std::{
+    collections::HashMap,
     fs::{self, create_dir_all, File},
     io::Write,
     path::Path,
@@ -9,12 +8,30 @@ use std::{
 };
 
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
 use csv::StringRecord;
-use hprtree::{BBox, HPRTree, HPRTreeBuilder, Point};
+use hprtree::{BBox, Point};
+
+mod count;
+mod shape;
+mod target;
+
+use count::CountTree;
+use shape::{Placement, Shape};
+use target::{Amount, Target};
 
 const ENV_SIZES: [usize; 5] = [16, 64, 256, 1024, 4096];
 const ENV_COUNT: usize = 16;
+// used when no seed is given on the command line
+const DEFAULT_SEED: u64 = 20230601;
+// seeds tried before giving up on an envelope that fits the shape
+const MAX_SEEDS: usize = 10_000;
+// the gen= header field, bumped whenever the same seed gives different files. files without it
+// are from the random walk generator, 1.
+const GENERATOR_VERSION: u32 = 2;
+// rounds of the edge by edge growth
+const EDGE_ROUNDS: usize = 32;
 const MAX_ENV: BBox = BBox {
     minx: -180f32,
     maxx: 180f32,
@@ -56,176 +73,350 @@ struct Element {
     pub id: u32,
 }
 
-fn build_hprtree<T>(
+fn build_counttree<T>(
     delimiter: u8,
     deser: fn(StringRecord) -> Option<(T, Point)>,
     count: usize,
     path: &str,
-) -> HPRTree<T>
+) -> CountTree {
+    let data = read(delimiter, path, count, deser);
+    CountTree::new(data.into_iter().map(|e| e.1).collect())
+}
+
+// the envelope files start with a "# key=value ..." line saying how they were made
+fn header(seed: u64, target: &Target, n: usize, shape: &Shape) -> String {
+    format!(
+        "# gen={GENERATOR_VERSION} seed={seed} {} {}\n",
+        target.header(n),
+        shape.header()
+    )
+}
+
+fn read_header(path: &Path) -> Option<HashMap<String, String>> {
+    let content = fs::read_to_string(path).ok()?;
+    let line = content.lines().next()?.strip_prefix("# ")?;
+    Some(
+        line.split_whitespace()
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    )
+}
+
+// one stream per target count, so every file can be regenerated on its own
+fn envelope_rng(seed: u64, count: usize) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(count as u64);
+    rng
+}
+
+fn copy_bbox(b: &BBox) -> BBox {
+    BBox {
+        minx: b.minx,
+        maxx: b.maxx,
+        miny: b.miny,
+        maxy: b.maxy,
+    }
+}
+
+fn same_bbox(a: &BBox, b: &BBox) -> bool {
+    a.minx == b.minx && a.maxx == b.maxx && a.miny == b.miny && a.maxy == b.maxy
+}
+
+// box around c with half height t and half width t * aspect, cut off at MAX_ENV
+fn envelope_around(c: &Point, t: f64, aspect: f64) -> BBox {
+    let (w, h) = (t * aspect, t);
+    BBox {
+        minx: ((c.x as f64 - w) as f32).max(MAX_ENV.minx),
+        maxx: ((c.x as f64 + w) as f32).min(MAX_ENV.maxx),
+        miny: ((c.y as f64 - h) as f32).max(MAX_ENV.miny),
+        maxy: ((c.y as f64 + h) as f32).min(MAX_ENV.maxy),
+    }
+}
+
+// env with one edge (minx, maxx, miny, maxy) moved outwards to v, the min edges as -v so the
+// box grows with v for all of them
+fn with_edge(env: &BBox, edge: usize, v: f64) -> BBox {
+    let mut env = copy_bbox(env);
+    match edge {
+        0 => env.minx = -v as f32,
+        1 => env.maxx = v as f32,
+        2 => env.miny = -v as f32,
+        _ => env.maxy = v as f32,
+    }
+    env
+}
+
+fn edge_of(env: &BBox, edge: usize) -> f64 {
+    match edge {
+        0 => -env.minx as f64,
+        1 => env.maxx as f64,
+        2 => -env.miny as f64,
+        _ => env.maxy as f64,
+    }
+}
+
+enum Bisection {
+    Found(BBox),
+    // no parameter gives a count within the bounds, the last ones with fewer and with more
+    Between(f64, f64),
+}
+
+// bisection over boxes that only grow with the parameter, with too few points at lo and too
+// many at hi. it ends when the f32 box doesn't change anymore, which is where points enter
+// together.
+fn bisect<F>(
+    tree: &CountTree,
+    bounds: (usize, usize),
+    steps: &mut usize,
+    lo: f64,
+    hi: f64,
+    f: F,
+) -> Bisection
 where
-    T: Clone,
+    F: Fn(f64) -> BBox,
 {
-    let data = read(delimiter, path, count, deser);
+    let (mut lo, mut hi) = (lo, hi);
+    loop {
+        let mid = lo + (hi - lo) / 2f64;
+        let env = f(mid);
+        if mid <= lo || mid >= hi || same_bbox(&env, &f(lo)) || same_bbox(&env, &f(hi)) {
+            return Bisection::Between(lo, hi);
+        }
+        *steps += 1;
+        match target::compare(bounds, tree.count(&env)) {
+            std::cmp::Ordering::Less => lo = mid,
+            std::cmp::Ordering::Greater => hi = mid,
+            std::cmp::Ordering::Equal => return Bisection::Found(env),
+        }
+    }
+}
 
-    let mut treebuilder = hprtree::HPRTreeBuilder::new(data.len());
-    for e in data {
-        treebuilder.insert(e.0, e.1);
+// grows a box around c until its count is within the bounds. if points entering together
+// overshoot, it grows again from c in small rounds and one edge at a time, which takes them a
+// column or row at a time while the box keeps its shape.
+fn search(
+    tree: &CountTree,
+    c: &Point,
+    aspect: f64,
+    bounds: (usize, usize),
+    steps: &mut usize,
+) -> Option<BBox> {
+    let around = |t| envelope_around(c, t, aspect);
+    // from just the seed to the whole world, not further so the box keeps changing with t
+    let w = (c.x - MAX_ENV.minx).max(MAX_ENV.maxx - c.x) as f64;
+    let h = (c.y - MAX_ENV.miny).max(MAX_ENV.maxy - c.y) as f64;
+    let (lo, hi) = (0f64, (w / aspect).max(h));
+    match target::compare(bounds, tree.count(&around(lo))) {
+        std::cmp::Ordering::Less => (),
+        std::cmp::Ordering::Equal => return Some(around(lo)),
+        std::cmp::Ordering::Greater => return None,
+    }
+    if target::compare(bounds, tree.count(&around(hi))).is_eq() {
+        return Some(around(hi));
     }
-    treebuilder.build()
+    let hi = match bisect(tree, bounds, steps, lo, hi, around) {
+        Bisection::Found(env) => return Some(env),
+        Bisection::Between(_, hi) => hi,
+    };
+
+    let mut env = around(lo);
+    for round in 1..=EDGE_ROUNDS {
+        let target = around(hi * round as f64 / EDGE_ROUNDS as f64);
+        for edge in [1, 3, 0, 2] {
+            let (lo, to) = (edge_of(&env, edge), edge_of(&target, edge));
+            if lo >= to {
+                continue;
+            }
+            let grown = with_edge(&env, edge, to);
+            *steps += 1;
+            match target::compare(bounds, tree.count(&grown)) {
+                std::cmp::Ordering::Less => {
+                    env = grown;
+                    continue;
+                }
+                std::cmp::Ordering::Equal => return Some(grown),
+                std::cmp::Ordering::Greater => (),
+            }
+            match bisect(tree, bounds, steps, lo, to, |v| with_edge(&env, edge, v)) {
+                Bisection::Found(env) => return Some(env),
+                Bisection::Between(v, _) => env = with_edge(&env, edge, v),
+            }
+        }
+    }
+    None
 }
 
-#[derive(PartialEq, Eq)]
-enum BBoxPart {
-    MINX,
-    MAXX,
-    MINY,
-    MAXY,
+// a box with a count within the bounds, placed and shaped as given
+fn find_envelope(
+    tree: &CountTree,
+    bounds: (usize, usize),
+    shape: &Shape,
+    rng: &mut ChaCha8Rng,
+    i: usize,
+) -> BBox {
+    assert!(tree.len() >= bounds.0);
+    let extent = tree.extent();
+    let start = time::Instant::now();
+    let mut steps = 0;
+    for attempt in 1..=MAX_SEEDS {
+        let c = match shape.placement {
+            Placement::Point => {
+                let p = tree.point(rng.gen_range(0..tree.len()));
+                Point { x: p.x, y: p.y }
+            }
+            Placement::Uniform => Point {
+                x: rng.gen_range(extent.minx..=extent.maxx),
+                y: rng.gen_range(extent.miny..=extent.maxy),
+            },
+        };
+        let aspect = shape.aspect.of(&c, rng);
+        let env = match search(tree, &c, aspect, bounds, &mut steps) {
+            Some(env) if shape.accepts(&env) => env,
+            _ => continue,
+        };
+        let diff = time::Instant::now() - start;
+        println!(
+            "\tfinal {i}: Envelope{{minx: {}, maxx: {}, miny: {}, maxy: {}}} ({attempt} seeds, {steps} steps in {diff:?})",
+            env.minx, env.maxx, env.miny, env.maxy
+        );
+        return env;
+    }
+    panic!(
+        "no envelope with {} to {} points and an area in [{}, {}] after {MAX_SEEDS} seeds",
+        bounds.0, bounds.1, shape.min_area, shape.max_area
+    );
 }
-use BBoxPart::*;
 
-fn gen_envelopes(tree: HPRTree<Element>, path: &str) {
-    let mut rng = thread_rng();
-    for size in ENV_SIZES {
-        let path = format!("{}.{}", path, size);
+// the whole file for one target, header included. the fifth column is the count of the
+// envelope, which can be off the target by the tolerance.
+fn envelope_file(tree: &CountTree, target: &Target, seed: u64, shape: &Shape) -> String {
+    let bounds = target.bounds(tree.len());
+    println!(
+        "target: {} ({} to {} points)",
+        target.amount.name(),
+        bounds.0,
+        bounds.1
+    );
+    let mut rng = envelope_rng(seed, target.count(tree.len()));
+    let mut content = header(seed, target, tree.len(), shape);
+    for i in 0..ENV_COUNT {
+        let env = find_envelope(tree, bounds, shape, &mut rng, i);
+        content += &format!(
+            "{},{},{},{},{}\n",
+            env.minx,
+            env.maxx,
+            env.miny,
+            env.maxy,
+            tree.count(&env)
+        );
+    }
+    content
+}
+
+fn gen_envelopes(tree: &CountTree, path: &str, seed: u64, targets: &[Target], shape: &Shape) {
+    for target in targets {
+        let path = format!("{}.{}{}", path, target.suffix(), shape.suffix());
         let p = Path::new(&path);
 
         if p.exists() {
             println!("skipping {p:?}, preexists");
             continue;
         }
+        if target.bounds(tree.len()).0 > tree.len() {
+            println!("skipping {p:?}, only {} points", tree.len());
+            continue;
+        }
 
-        let mut envelopes = Vec::with_capacity(ENV_COUNT);
-        println!("target: {size}");
-
-        for i in 0..ENV_COUNT {
-            let mut env = tree.extent();
-            let mut too_small = tree.query(&env).len() < size;
-
-            let mut loop_count = 0;
-            let mut info = false;
-            let start = time::Instant::now();
-            loop {
-                if info {
-                    println!(
-                    "\tattempt {i}: Envelope{{minx: {}, maxx: {}, miny: {}, maxy: {}}} ({}, {})",
-                    env.minx,
-                    env.maxx,
-                    env.miny,
-                    env.maxy,
-                    tree.query(&env).len(),
-                    if too_small { "too small" } else { "too big" }
-                );
-                }
-                let mut parts = vec![MINX, MAXX, MINY, MAXY];
+        let content = envelope_file(tree, target, seed, shape);
+        create_dir_all(p.parent().unwrap()).unwrap();
+        let mut file = File::create(p).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+}
 
-                if too_small {
-                    if env.minx <= MAX_ENV.minx {
-                        parts.retain(|e| *e != MINX);
-                    }
-                    if env.miny <= MAX_ENV.miny {
-                        parts.retain(|e| *e != MINY);
-                    }
-                    if env.maxx >= MAX_ENV.maxx {
-                        parts.retain(|e| *e != MAXX);
-                    }
-                    if env.maxy >= MAX_ENV.maxy {
-                        parts.retain(|e| *e != MAXY);
-                    }
-                } else {
-                    if env.minx >= env.maxx {
-                        parts.retain(|e| *e != MINX);
-                    }
-                    if env.miny >= env.maxy {
-                        parts.retain(|e| *e != MINY);
-                    }
-                    if env.maxx <= env.minx {
-                        parts.retain(|e| *e != MAXX);
-                    }
-                    if env.maxy <= env.miny {
-                        parts.retain(|e| *e != MAXY);
-                    }
-                }
-                assert!(parts.len() != 0);
-
-                match parts.choose(&mut rng).unwrap() {
-                    MINX => {
-                        let max_delta = if too_small { MAX_ENV.minx } else { env.maxx } - env.minx;
-                        if info {
-                            println!("\tmax delta minx: {max_delta}");
-                        }
-                        let delta = rng.gen_range(
-                            0f32.next_up().min(max_delta)..max_delta.max(0f32.next_up()),
-                        );
-                        env.minx += delta;
-                    }
-                    MINY => {
-                        let max_delta = if too_small { MAX_ENV.miny } else { env.maxy } - env.miny;
-                        if info {
-                            println!("\tmax delta miny: {max_delta}");
-                        }
-                        let delta = rng.gen_range(
-                            0f32.next_up().min(max_delta)..max_delta.max(0f32.next_up()),
-                        );
-                        env.miny += delta;
-                    }
-                    MAXX => {
-                        let max_delta = if too_small { MAX_ENV.maxx } else { env.minx } - env.maxx;
-                        if info {
-                            println!("\tmax delta maxx: {max_delta}");
-                        }
-                        let delta = rng.gen_range(
-                            0f32.next_up().min(max_delta)..max_delta.max(0f32.next_up()),
-                        );
-                        env.maxx += delta;
-                    }
-                    MAXY => {
-                        let max_delta = if too_small { MAX_ENV.maxy } else { env.miny } - env.maxy;
-                        if info {
-                            println!("\tmax delta maxy: {max_delta}");
-                        }
-                        let delta = rng.gen_range(
-                            0f32.next_up().min(max_delta)..max_delta.max(0f32.next_up()),
-                        );
-                        env.maxy += delta;
-                    }
-                }
+// the envelope files next to path that belong to it, whatever their target
+fn envelope_files(path: &str) -> Vec<String> {
+    let p = Path::new(path);
+    let prefix = format!("{}.", p.file_name().unwrap().to_str().unwrap());
+    let mut files: Vec<String> = match fs::read_dir(p.parent().unwrap()) {
+        Ok(entries) => entries
+            .map(|e| e.unwrap().file_name().to_str().unwrap().to_string())
+            .filter(|name| name.starts_with(&prefix))
+            .map(|name| format!("{}.{}", path, &name[prefix.len()..]))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
 
-                let count = tree.query(&env).len();
-                too_small = match count.cmp(&size) {
-                    std::cmp::Ordering::Less => true,
-                    std::cmp::Ordering::Greater => false,
-                    std::cmp::Ordering::Equal => break,
-                };
-                loop_count += 1;
-                if info {
-                    info = false;
-                } else if loop_count % 100000 == 0 {
-                    println!("\titeration {loop_count}");
-                    info = true;
-                }
+// regenerates every existing file of the dataset from the seed and target in its header, false
+// if one can't be verified or comes out different
+fn verify_envelopes(tree: &CountTree, path: &str) -> bool {
+    let mut ok = true;
+    let files = envelope_files(path);
+    if files.is_empty() {
+        println!("skipping {path:?}, no envelopes");
+    }
+    for file in files {
+        let p = Path::new(&file);
+        let header = read_header(p).unwrap_or_default();
+        let gen = header.get("gen").map_or(Some(1), |g| g.parse::<u32>().ok());
+        match gen {
+            Some(GENERATOR_VERSION) => (),
+            Some(g) if g < GENERATOR_VERSION => {
+                println!("{p:?} was generated by an older version (gen={g}), regenerate it");
+                ok = false;
+                continue;
+            }
+            _ => {
+                println!("{p:?} was generated by an unknown version");
+                ok = false;
+                continue;
             }
-            let end = time::Instant::now();
-            let diff = end - start;
-            println!(
-                "\tfinal {i}: Envelope{{minx: {}, maxx: {}, miny: {}, maxy: {}}} ({loop_count} iter in {diff:?})",
-                env.minx, env.maxx, env.miny, env.maxy
-            );
-            envelopes.push(env);
         }
-
-        create_dir_all(p.parent().unwrap()).unwrap();
-        let mut file = File::create(p).unwrap();
-        for env in envelopes {
-            file.write_fmt(format_args!(
-                "{},{},{},{}\n",
-                env.minx, env.maxx, env.miny, env.maxy
-            ))
-            .unwrap();
+        let seed = match header.get("seed").and_then(|s| s.parse::<u64>().ok()) {
+            Some(seed) => seed,
+            None => {
+                println!("{p:?} has no seed, can't verify");
+                ok = false;
+                continue;
+            }
+        };
+        let shape = match Shape::from_header(&header) {
+            Some(shape) => shape,
+            None => {
+                println!("{p:?} has an unknown shape");
+                ok = false;
+                continue;
+            }
+        };
+        let target = match Target::from_header(&header) {
+            Some(target) if file == format!("{}.{}{}", path, target.suffix(), shape.suffix()) => {
+                target
+            }
+            _ => {
+                println!("{p:?} has the wrong target or shape for its name");
+                ok = false;
+                continue;
+            }
+        };
+        if fs::read_to_string(p).unwrap() == envelope_file(tree, &target, seed, &shape) {
+            println!("{p:?} ok");
+        } else {
+            println!("{p:?} differs from seed {seed}");
+            ok = false;
         }
     }
+    ok
 }
 
-fn main() {
+// calls f(tree, envelope file) for every dataset
+fn for_each_dataset<F>(mut f: F)
+where
+    F: FnMut(&CountTree, &str),
+{
     let opendata_to_element = |record: StringRecord| {
         assert!(record.len() == 20);
         let geoid = record.get(0).unwrap().parse::<u32>().unwrap();
@@ -294,8 +485,8 @@ fn main() {
         Some((Element { lat, lon, id }, hprtree::Point { x: lon, y: lat }))
     };
 
-    gen_envelopes(
-        build_hprtree(
+    f(
+        &build_counttree(
             b',',
             matthe_to_element,
             3808651,
@@ -303,8 +494,8 @@ fn main() {
         ),
         "../../../data/envelopes/base/matthewproctor/worldcities-geo.csv",
     );
-    gen_envelopes(
-        build_hprtree(
+    f(
+        &build_counttree(
             b';',
             opendata_to_element,
             140974,
@@ -312,8 +503,8 @@ fn main() {
         ),
         "../../../data/envelopes/base/opendatasoft/geonames-all-cities-with-a-population-1000.csv",
     );
-    gen_envelopes(
-        build_hprtree(
+    f(
+        &build_counttree(
             b',',
             simplemaps_to_element,
             44692,
@@ -338,8 +529,8 @@ fn main() {
             let count = numelem * numclust;
             let p = path.path();
             let strpath = p.to_str().unwrap();
-            gen_envelopes(
-                build_hprtree(b',', random_to_element, count, strpath),
+            f(
+                &build_counttree(b',', random_to_element, count, strpath),
                 strpath.replace("/data", "/data/envelopes").as_str(),
             );
         }
@@ -363,8 +554,8 @@ fn main() {
                 .0
                 .parse::<usize>()
                 .unwrap();
-            gen_envelopes(
-                build_hprtree(b',', random_to_element, count, strpath),
+            f(
+                &build_counttree(b',', random_to_element, count, strpath),
                 strpath.replace("/data", "/data/envelopes").as_str(),
             );
         }
@@ -373,22 +564,70 @@ fn main() {
         for mult in [1, 4, 16, 64, 256] {
             let submult = (mult as f32).sqrt();
             let d = 2f32 / submult;
-            let mut builder = HPRTreeBuilder::new(180 * 90 * mult);
+            let mut points = Vec::with_capacity(180 * 90 * mult);
             let mut x = -180f32;
             for i in 0..(180 * submult as u32) {
                 let mut y = -90f32;
                 for j in 0..(90 * submult as u32) {
                     let elem = synthetic_to_element(x, y, i * 10000u32 + j);
-                    builder.insert(elem.0, elem.1);
+                    points.push(elem.1);
                     y += d;
                 }
                 x += d;
             }
-            assert!(builder.len() == 180 * 90 * mult);
-            gen_envelopes(
-                builder.build(),
+            assert!(points.len() == 180 * 90 * mult);
+            f(
+                &CountTree::new(points),
                 format!("../../../data/envelopes/ordered/{}", mult).as_str(),
             );
         }
     }
 }
+
+// genenvelopes [seed] [--targets <n>%,...|<n>,...] [--tolerance <n>|<n>%]
+// [--aspect random|square|km|ratio:<w/h>] [--placement point|uniform] [--min-area <deg2>]
+// [--max-area <deg2>] generates the missing files, the targets default to ENV_SIZES and
+// percentages of them are of the dataset size, those of the tolerance of the target count.
+// shapes other than the default get their own files, named like "0.1pct-square-uniform".
+// genenvelopes verify regenerates the existing files from their headers and compares.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("verify") => {
+            let mut ok = true;
+            for_each_dataset(|tree, path| ok &= verify_envelopes(tree, path));
+            if !ok {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            let (seed, options) = match args.first() {
+                Some(seed) if !seed.starts_with("--") => (seed.parse().unwrap(), &args[1..]),
+                _ => (DEFAULT_SEED, &args[..]),
+            };
+            let mut amounts: Vec<Amount> = ENV_SIZES.iter().map(|&s| Amount::Count(s)).collect();
+            let mut tolerance = Amount::Count(0);
+            let mut shape_options = Vec::new();
+            for pair in options.chunks(2) {
+                match (pair[0].as_str(), pair.get(1)) {
+                    ("--targets", Some(v)) => {
+                        amounts = v
+                            .split(',')
+                            .map(|t| Amount::parse(t).expect("bad target"))
+                            .collect()
+                    }
+                    ("--tolerance", Some(v)) => {
+                        tolerance = Amount::parse(v).expect("bad tolerance")
+                    }
+                    _ => shape_options.extend_from_slice(pair),
+                }
+            }
+            let targets: Vec<Target> = amounts
+                .into_iter()
+                .map(|amount| Target { amount, tolerance })
+                .collect();
+            let shape = Shape::from_args(&shape_options).expect("bad shape options");
+            for_each_dataset(|tree, path| gen_envelopes(tree, path, seed, &targets, &shape));
+        }
+    }
+}
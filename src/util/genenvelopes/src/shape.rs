@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use hprtree::{BBox, Point};
+use rand::Rng;
+
+// width to height of the envelopes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aspect {
+    // log uniform between 1:4 and 4:1
+    Random,
+    // as wide as high in degrees
+    Square,
+    // as wide as high in kilometres at the centre's latitude
+    SquareKm,
+    // this width / height in degrees
+    Ratio(f64),
+}
+
+impl Aspect {
+    pub fn name(&self) -> String {
+        match self {
+            Aspect::Random => "random".to_string(),
+            Aspect::Square => "square".to_string(),
+            Aspect::SquareKm => "km".to_string(),
+            Aspect::Ratio(r) => format!("ratio:{r}"),
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "random" => Some(Aspect::Random),
+            "square" => Some(Aspect::Square),
+            "km" => Some(Aspect::SquareKm),
+            _ => {
+                let r = s.strip_prefix("ratio:")?.parse::<f64>().ok()?;
+                (r > 0f64).then_some(Aspect::Ratio(r))
+            }
+        }
+    }
+
+    // width / height in degrees of a box around c
+    pub fn of<R: Rng>(&self, c: &Point, rng: &mut R) -> f64 {
+        match *self {
+            Aspect::Random => rng.gen_range(-2f64..2f64).exp2(),
+            Aspect::Square => 1f64,
+            // a degree of longitude shrinks with the cosine of the latitude, the poles get the
+            // whole width
+            Aspect::SquareKm => 1f64 / (c.y as f64).to_radians().cos().max(0.01f64),
+            Aspect::Ratio(r) => r,
+        }
+    }
+}
+
+// where the envelopes are centred
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placement {
+    // on a random data point, so they follow the data
+    Point,
+    // uniformly in the extent of the data
+    Uniform,
+}
+
+impl Placement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placement::Point => "point",
+            Placement::Uniform => "uniform",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "point" => Some(Placement::Point),
+            "uniform" => Some(Placement::Uniform),
+            _ => None,
+        }
+    }
+}
+
+// everything about the envelopes besides their count, recorded in the file headers
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shape {
+    pub aspect: Aspect,
+    pub placement: Placement,
+    // in square degrees, envelopes outside are thrown away
+    pub min_area: f64,
+    pub max_area: f64,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape {
+            aspect: Aspect::Random,
+            placement: Placement::Point,
+            min_area: 0f64,
+            max_area: f64::INFINITY,
+        }
+    }
+}
+
+impl Shape {
+    // the key=value fields of the header
+    pub fn header(&self) -> String {
+        format!(
+            "aspect={} placement={} min_area={} max_area={}",
+            self.aspect.name(),
+            self.placement.name(),
+            self.min_area,
+            self.max_area
+        )
+    }
+
+    // what comes after the target in the file name. empty for the default shape, so those
+    // files keep their plain names, else the parts that differ, like "-square-uniform" or
+    // "-area0.01to1".
+    pub fn suffix(&self) -> String {
+        let default = Shape::default();
+        let mut suffix = String::new();
+        if self.aspect != default.aspect {
+            suffix += &format!("-{}", self.aspect.name().replace(':', ""));
+        }
+        if self.placement != default.placement {
+            suffix += &format!("-{}", self.placement.name());
+        }
+        if self.min_area != default.min_area || self.max_area != default.max_area {
+            suffix += &format!("-area{}to{}", self.min_area, self.max_area);
+        }
+        suffix
+    }
+
+    // fields that are missing get the defaults
+    pub fn from_header(fields: &HashMap<String, String>) -> Option<Self> {
+        let mut shape = Shape::default();
+        for (k, v) in fields {
+            shape.set(k, v)?;
+        }
+        Some(shape)
+    }
+
+    // --aspect, --placement, --min-area and --max-area with their values
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let mut shape = Shape::default();
+        for pair in args.chunks(2) {
+            let key = pair[0].strip_prefix("--")?.replace('-', "_");
+            if !["aspect", "placement", "min_area", "max_area"].contains(&key.as_str()) {
+                return None;
+            }
+            shape.set(&key, pair.get(1)?)?;
+        }
+        Some(shape)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Option<()> {
+        match key {
+            "aspect" => self.aspect = Aspect::parse(value)?,
+            "placement" => self.placement = Placement::parse(value)?,
+            "min_area" => self.min_area = value.parse().ok()?,
+            "max_area" => self.max_area = value.parse().ok()?,
+            // seed and target
+            _ => (),
+        }
+        Some(())
+    }
+
+    pub fn accepts(&self, env: &BBox) -> bool {
+        let area = (env.maxx - env.minx) as f64 * (env.maxy - env.miny) as f64;
+        area >= self.min_area && area <= self.max_area
+    }
+}
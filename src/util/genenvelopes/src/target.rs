@@ -0,0 +1,109 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// a number of points, either as it is or in percent of some base
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Amount {
+    Count(usize),
+    Percent(f64),
+}
+
+impl Amount {
+    // "16" or "0.1%"
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.strip_suffix('%') {
+            Some(p) => {
+                let p = p.parse::<f64>().ok()?;
+                (p >= 0f64).then_some(Amount::Percent(p))
+            }
+            None => s.parse().ok().map(Amount::Count),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Amount::Count(c) => c.to_string(),
+            Amount::Percent(p) => format!("{p}%"),
+        }
+    }
+
+    // for file names, "16" or "0.1pct"
+    pub fn suffix(&self) -> String {
+        match self {
+            Amount::Count(c) => c.to_string(),
+            Amount::Percent(p) => format!("{p}pct"),
+        }
+    }
+
+    pub fn of(&self, base: usize) -> usize {
+        match *self {
+            Amount::Count(c) => c,
+            Amount::Percent(p) => (base as f64 * p / 100f64).round() as usize,
+        }
+    }
+}
+
+// how many points the envelopes of a file should hold, the percentages of a target are of the
+// dataset size, those of a tolerance of the target count
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target {
+    pub amount: Amount,
+    pub tolerance: Amount,
+}
+
+impl Target {
+    // what comes after the dataset name in the file name, "16" or "0.1pct". a tolerance is
+    // added as "pm3" or "pm25pct", so only exact files have the plain names the benches expect
+    // exact counts from.
+    pub fn suffix(&self) -> String {
+        let tolerance = match self.tolerance {
+            Amount::Count(0) => String::new(),
+            Amount::Percent(0f64) => String::new(),
+            tolerance => format!("pm{}", tolerance.suffix()),
+        };
+        format!("{}{tolerance}", self.amount.suffix())
+    }
+
+    // the point count aimed at for n points
+    pub fn count(&self, n: usize) -> usize {
+        self.amount.of(n).max(1)
+    }
+
+    // fewest and most points an envelope may hold
+    pub fn bounds(&self, n: usize) -> (usize, usize) {
+        let count = self.count(n);
+        let tolerance = self.tolerance.of(count);
+        (count.saturating_sub(tolerance).max(1), count + tolerance)
+    }
+
+    // the key=value fields of the header, count is informational
+    pub fn header(&self, n: usize) -> String {
+        format!(
+            "target={} tolerance={} count={}",
+            self.amount.name(),
+            self.tolerance.name(),
+            self.count(n)
+        )
+    }
+
+    pub fn from_header(fields: &HashMap<String, String>) -> Option<Self> {
+        Some(Target {
+            amount: Amount::parse(fields.get("target")?)?,
+            tolerance: match fields.get("tolerance") {
+                Some(t) => Amount::parse(t)?,
+                None => Amount::Count(0),
+            },
+        })
+    }
+}
+
+// Less if a box with count points is too small for the bounds, Greater if too big
+pub fn compare(bounds: (usize, usize), count: usize) -> Ordering {
+    if count < bounds.0 {
+        Ordering::Less
+    } else if count > bounds.1 {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}